@@ -0,0 +1,100 @@
+// Copyright (C) 2016-2017 Symtern Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-Apache
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or
+// distributed except according to those terms.
+//! Symtern benchmarks for interning from several threads at once.
+//!
+//! Each benchmark iteration spawns a number of threads that intern the same
+//! set of short random strings, starting at different offsets, into a single
+//! shared pool.  `ConcurrentPool` is compared against a basic `Pool` behind a
+//! `Mutex`.
+#![feature(test)]
+extern crate test;
+extern crate rand;
+extern crate symtern;
+#[macro_use] extern crate lazy_static;
+
+use std::sync::Mutex;
+use std::thread;
+
+use rand::Rng;
+use test::Bencher;
+use symtern::prelude::*;
+use symtern::{Pool, ConcurrentPool};
+
+const TEST_STRING_CHARS: [char; 26] = ['a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z'];
+
+lazy_static! {
+    static ref TEST_STRINGS_8: Vec<String> = generate_strings(20_000, 8);
+}
+
+fn generate_strings(n: usize, length: usize) -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    (0..n).map(|_| (0..length).map(|_| TEST_STRING_CHARS[rng.gen::<usize>() % TEST_STRING_CHARS.len()]).collect())
+        .collect()
+}
+
+/// Run `f(offset)` on each of `n_threads` threads and wait for them to
+/// finish.
+fn run_threads<F>(n_threads: usize, f: F)
+    where F: Fn(usize) + Sync
+{
+    let f = &f;
+    thread::scope(|scope| {
+        for t in 0..n_threads {
+            scope.spawn(move || f(t * TEST_STRINGS_8.len() / n_threads));
+        }
+    });
+}
+
+macro_rules! bench_concurrent_fn {
+    ($name: ident, $n_threads: expr) => {
+        #[bench]
+        fn $name(b: &mut Bencher) {
+            let strings: &[String] = &TEST_STRINGS_8[..];
+            b.bytes = (strings.len() * $n_threads) as u64;
+            b.iter(|| {
+                let pool = ConcurrentPool::<str, u32>::new();
+                run_threads($n_threads, |offset| {
+                    for s in strings[offset..].iter().chain(&strings[..offset]) {
+                        test::black_box((&pool).intern(s).expect("failed to intern value"));
+                    }
+                });
+                pool
+            });
+        }
+    };
+}
+
+macro_rules! bench_mutex_fn {
+    ($name: ident, $n_threads: expr) => {
+        #[bench]
+        fn $name(b: &mut Bencher) {
+            let strings: &[String] = &TEST_STRINGS_8[..];
+            b.bytes = (strings.len() * $n_threads) as u64;
+            b.iter(|| {
+                let pool = Mutex::new(Pool::<str, u32>::new());
+                run_threads($n_threads, |offset| {
+                    for s in strings[offset..].iter().chain(&strings[..offset]) {
+                        let mut pool = pool.lock().unwrap();
+                        test::black_box(pool.intern(s).expect("failed to intern value"));
+                    }
+                });
+                pool
+            });
+        }
+    };
+}
+
+bench_concurrent_fn!(concurrent_intern_1_thread, 1);
+bench_concurrent_fn!(concurrent_intern_2_threads, 2);
+bench_concurrent_fn!(concurrent_intern_4_threads, 4);
+bench_concurrent_fn!(concurrent_intern_8_threads, 8);
+
+bench_mutex_fn!(mutex_intern_1_thread, 1);
+bench_mutex_fn!(mutex_intern_2_threads, 2);
+bench_mutex_fn!(mutex_intern_4_threads, 4);
+bench_mutex_fn!(mutex_intern_8_threads, 8);
@@ -0,0 +1,106 @@
+// Copyright (C) 2016 Symtern Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-Apache
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or
+// distributed except according to those terms.
+//! Symtern memory-footprint benchmarks.
+//!
+//! Each of these fills a pool with a set of random strings, and prints the
+//! number of heap bytes held by the pool per interned string.  The timing
+//! results are meaningless.  These live in their own bench target because
+//! they install a counting global allocator, which would otherwise slow down
+//! every other benchmark.
+#![feature(test)]
+extern crate test;
+extern crate rand;
+extern crate symtern;
+#[macro_use] extern crate lazy_static;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rand::Rng;
+use test::Bencher;
+use symtern::prelude::*;
+use symtern::{Pool, ArenaPool};
+
+/// Allocator wrapper that keeps track of the number of bytes currently
+/// allocated, so we can compare the memory footprints of different pools.
+struct CountingAlloc;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const TEST_STRING_CHARS: [char; 26] = ['a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z'];
+
+lazy_static! {
+    static ref TEST_STRINGS_8: Vec<String> = generate_strings(100_000, 8);
+    static ref TEST_STRINGS_16: Vec<String> = generate_strings(100_000, 16);
+}
+
+fn generate_string(dest: &mut String, length: usize, chars: &[char]) {
+    dest.clear();
+    let mut rng = rand::thread_rng();
+    for _ in 0..length {
+        dest.push(chars[rng.gen::<usize>() % chars.len()]);
+    }
+}
+
+fn generate_strings(n: usize, length: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut s = String::with_capacity(length);
+    for _ in 0..n {
+        generate_string(&mut s, length, &TEST_STRING_CHARS);
+        out.push(s.clone());
+    }
+    out
+}
+
+macro_rules! bench_footprint_fn {
+    ($name: ident, $new: expr, $strings_set: ident) => {
+        #[bench]
+        fn $name(b: &mut Bencher) {
+            // (Force generation of the test strings before we start counting.)
+            let strings: &[String] = &$strings_set[..];
+            let before = ALLOCATED.load(Ordering::Relaxed);
+            let mut pool = $new;
+            for s in strings.iter() {
+                pool.intern(&s[..]).expect("failed to intern string");
+            }
+            // Allocations freed by other threads in the meantime could make
+            // the count drop below where it started.
+            let footprint = ALLOCATED.load(Ordering::Relaxed).saturating_sub(before);
+            // (Write to stderr directly, since libtest captures `eprintln!`.)
+            let _ = writeln!(io::stderr(), "{}: {} bytes/string", stringify!($name), footprint / strings.len());
+            b.iter(|| pool.len());
+        }
+    };
+}
+
+bench_footprint_fn!(footprint_basic_8 , Pool::<str,u64>::new(), TEST_STRINGS_8);
+bench_footprint_fn!(footprint_basic_16, Pool::<str,u64>::new(), TEST_STRINGS_16);
+bench_footprint_fn!(footprint_arena_8 , ArenaPool::<u64>::new(), TEST_STRINGS_8);
+bench_footprint_fn!(footprint_arena_16, ArenaPool::<u64>::new(), TEST_STRINGS_16);
@@ -15,8 +15,8 @@ extern crate symtern;
 use rand::Rng;
 use test::Bencher;
 use symtern::prelude::*;
-use symtern::Pool;
-use symtern::adaptors::Inline;
+use symtern::{Pool, ArenaPool};
+use symtern::adaptors::{Inline, Alphabet6};
 
 const TEST_STRING_CHARS: [char; 26] = ['a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z'];
 
@@ -29,6 +29,7 @@ lazy_static! {
     static ref TEST_STRINGS_7: Vec<String> = generate_strings(400_000, 7);
     static ref TEST_STRINGS_8: Vec<String> = generate_strings(100_000, 8);
 
+    static ref TEST_STRINGS_15: Vec<String> = generate_strings(100_000, 15);
     static ref TEST_STRINGS_16: Vec<String> = generate_strings(100_000, 16);
     static ref TEST_STRINGS_32: Vec<String> = generate_strings(100_000, 32);
 }
@@ -92,6 +93,11 @@ bench_intern_fn!(intern_basic_8 , Pool::<str,u64>::new()  , TEST_STRINGS_8, 8);
 bench_intern_fn!(intern_basic_16, Pool::<str,u64>::new()  , TEST_STRINGS_16, 16);
 bench_intern_fn!(intern_basic_32, Pool::<str,u64>::new()  , TEST_STRINGS_16, 32);
 
+bench_intern_fn!(intern_arena_4 , ArenaPool::<u64>::new()  , TEST_STRINGS_4, 4);
+bench_intern_fn!(intern_arena_8 , ArenaPool::<u64>::new()  , TEST_STRINGS_8, 8);
+bench_intern_fn!(intern_arena_16, ArenaPool::<u64>::new()  , TEST_STRINGS_16, 16);
+bench_intern_fn!(intern_arena_32, ArenaPool::<u64>::new()  , TEST_STRINGS_16, 32);
+
 bench_intern_fn!(intern_short_2 , Inline::<Pool<str,u64>>::new()      , TEST_STRINGS_2, 2);
 bench_intern_fn!(intern_short_3 , Inline::<Pool<str,u64>>::new()      , TEST_STRINGS_3, 3);
 bench_intern_fn!(intern_short_4 , Inline::<Pool<str,u64>>::new()      , TEST_STRINGS_4, 4);
@@ -102,12 +108,35 @@ bench_intern_fn!(intern_short_8 , Inline::<Pool<str,u64>>::new()      , TEST_STR
 bench_intern_fn!(intern_short_16, Inline::<Pool<str,u64>>::new()      , TEST_STRINGS_16, 16);
 bench_intern_fn!(intern_short_32, Inline::<Pool<str,u64>>::new()      , TEST_STRINGS_16, 32);
 
+// `u128` IDs inline strings of up to 15 bytes; 16-byte strings go to the
+// wrapped pool.
+bench_intern_fn!(intern_short128_8 , Inline::<Pool<str,u128>>::new()  , TEST_STRINGS_8, 8);
+bench_intern_fn!(intern_short128_15, Inline::<Pool<str,u128>>::new()  , TEST_STRINGS_15, 15);
+bench_intern_fn!(intern_short128_16, Inline::<Pool<str,u128>>::new()  , TEST_STRINGS_16, 16);
+
+// `Alphabet6` inlines identifier-like strings of up to 10 characters in a
+// `u64`, and up to 21 in a `u128`.
+bench_intern_fn!(intern_alphabet6_8 , Inline::<Pool<str,u64>, Alphabet6>::new() , TEST_STRINGS_8, 8);
+bench_intern_fn!(intern_alphabet6_16, Inline::<Pool<str,u128>, Alphabet6>::new(), TEST_STRINGS_16, 16);
+
 bench_resolve_fn!(resolve_basic_4 , Pool::<str,u64>::new(), TEST_STRINGS_4);
 bench_resolve_fn!(resolve_basic_8 , Pool::<str,u64>::new(), TEST_STRINGS_8);
 bench_resolve_fn!(resolve_basic_16, Pool::<str,u64>::new(), TEST_STRINGS_16);
 bench_resolve_fn!(resolve_basic_32, Pool::<str,u64>::new(), TEST_STRINGS_32);
 
+bench_resolve_fn!(resolve_arena_4 , ArenaPool::<u64>::new(), TEST_STRINGS_4);
+bench_resolve_fn!(resolve_arena_8 , ArenaPool::<u64>::new(), TEST_STRINGS_8);
+bench_resolve_fn!(resolve_arena_16, ArenaPool::<u64>::new(), TEST_STRINGS_16);
+bench_resolve_fn!(resolve_arena_32, ArenaPool::<u64>::new(), TEST_STRINGS_32);
+
 bench_resolve_fn!(resolve_short_4 , Inline::<Pool<str,u64>>::new()    , TEST_STRINGS_4, resolve_ref);
 bench_resolve_fn!(resolve_short_8 , Inline::<Pool<str,u64>>::new()    , TEST_STRINGS_8, resolve_ref);
 bench_resolve_fn!(resolve_short_16, Inline::<Pool<str,u64>>::new()    , TEST_STRINGS_16, resolve_ref);
 bench_resolve_fn!(resolve_short_32, Inline::<Pool<str,u64>>::new()    , TEST_STRINGS_32, resolve_ref);
+
+bench_resolve_fn!(resolve_short128_8 , Inline::<Pool<str,u128>>::new(), TEST_STRINGS_8, resolve_ref);
+bench_resolve_fn!(resolve_short128_15, Inline::<Pool<str,u128>>::new(), TEST_STRINGS_15, resolve_ref);
+bench_resolve_fn!(resolve_short128_16, Inline::<Pool<str,u128>>::new(), TEST_STRINGS_16, resolve_ref);
+
+bench_resolve_fn!(resolve_alphabet6_8 , Inline::<Pool<str,u64>, Alphabet6>::new() , TEST_STRINGS_8, resolve_ref);
+bench_resolve_fn!(resolve_alphabet6_16, Inline::<Pool<str,u128>, Alphabet6>::new(), TEST_STRINGS_16, resolve_ref);
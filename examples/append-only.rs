@@ -0,0 +1,23 @@
+// Copyright (C) 2016 Symtern Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-Apache
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or
+// distributed except according to those terms.
+//! Example of using Symtern's `AppendPool`, which allows references returned
+//! by `resolve` to be held while new values are interned.
+extern crate symtern;
+use symtern::prelude::*;
+use symtern::AppendPool;
+
+fn main() {
+    let pool = AppendPool::<str, u32>::new();
+    let x = pool.intern("foo").expect("failed to intern a value");
+    let foo = pool.resolve(x).expect("failed to resolve the value we just interned");
+
+    // We can keep using `foo` while interning new values.
+    let y = pool.intern("bar").expect("failed to intern a value");
+    assert_eq!("foo", foo);
+    assert_eq!(Ok("bar"), pool.resolve(y));
+}
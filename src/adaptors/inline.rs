@@ -7,112 +7,74 @@
 // distributed except according to those terms.
 //! Interner adaptor that uses the short-string optimization.
 // [Module documentation lives on the exported adaptor, `Inline`.]
-use std::{mem, str};
+use std::any::Any;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use num_traits::ToPrimitive;
-
-use crate::traits::{Intern, Resolve, Len, SymbolId};
+use crate::traits::{Intern, Lookup, Remove, Resolve, Iterate, Len, Capacity, Clear, Stats, PoolStats, SymbolId};
 use crate::{ErrorKind, Result};
 use crate::sym::{self, Symbol};
-
-/// Interface used to pack strings into symbol-IDs.  Any implementations of
-/// this trait *must* store inlined-string length in the most-significant
-/// _byte_ of the implementing type.
-#[doc(hidden)]
-pub trait Pack: Sized + PartialOrd {
-    /// Check if the value contains an inlined string slice.
-    fn is_inlined(&self) -> bool {
-        *self >= Self::msb_mask()
-    }
-
-    /// Get a mask for the most-significant-bit in the implementor.
-    fn msb_mask() -> Self;
-
-    /// Pack a string slice into an instance of the implementing type,
-    /// returning `Some(packed_value)`, or `None` if the slice is too long.
-    fn pack(s: &str) -> Option<Self>;
-
-    /// Fetch a reference to the inlined string slice, if any.
-    fn get_packed_ref(&self) -> Option<&str>;
+use crate::fmt::DebugResolver;
+use super::pack::{Bytes, DebugPack, InlineId, Pack};
+
+/// Symbol type used by the [`Inline`](struct.Inline.html) adaptor.  `E` is
+/// the adaptor's encoder, which lets inlined symbols format their values on
+/// their own.
+pub struct Sym<S, E = Bytes> {
+    wrapped: S,
+    encoder: PhantomData<E>,
 }
 
-/// Create a mask value for the most significant _bit_ in an $N-_byte_
-/// unsigned integer.
-macro_rules! msb_mask {
-    ($T: tt, $N: expr) => ( (1 as $T) << ($N * 8 - 1) );
-}
+// These are implemented by hand since encoders need not implement any of the
+// derivable traits.
+impl<S: Copy, E> Copy for Sym<S, E> {}
 
-#[test]
-fn test_msb_mask() {
-    assert_eq!(1 << 7, msb_mask!(u8, 1));
-    assert_eq!(1 << 15, msb_mask!(u16, 2));
-    assert_eq!(1 << 31, msb_mask!(u32, 4));
-    assert_eq!(1u64 << 63, msb_mask!(u64, 8));
+impl<S: Clone, E> Clone for Sym<S, E> {
+    fn clone(&self) -> Self {
+        self.wrapped.clone().into()
+    }
 }
 
-macro_rules! impl_pack {
-    ($T: tt, $N: expr) => {
-        impl Pack for $T {
-            fn msb_mask() -> Self {
-                msb_mask!($T, $N)
-            }
-
-            #[cfg(target_endian = "little")]
-            fn pack(s: &str) -> Option<Self> {
-                if s.len() >= $N { return None; }
-
-                let mut bytes = [0u8; $N];
-                bytes[0..s.len()].copy_from_slice(s.as_ref());
-                bytes[$N - 1] = s.len() as u8 | 0x80;
-
-                Some(unsafe { mem::transmute(bytes) })
-            }
-            #[cfg(target_endian = "big")]
-            fn pack(s: &str) -> Option<Self> {
-                if s.len() >= $N { return None; }
+impl<S: PartialEq, E> PartialEq for Sym<S, E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.wrapped == other.wrapped
+    }
+}
 
-                let mut bytes = [0u8; $N];
-                bytes[1..(s.len() + 1)].copy_from_slice(s.as_ref());
-                bytes[0] = s.len() as u8 | 0x80;
+impl<S: Eq, E> Eq for Sym<S, E> {}
 
-                Some(unsafe { mem::transmute(bytes) })
-            }
+impl<S: Hash, E> Hash for Sym<S, E> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.wrapped.hash(state)
+    }
+}
 
-            #[cfg(target_endian = "little")]
-            fn get_packed_ref(&self) -> Option<&str> {
-                if ! self.is_inlined() { return None; }
-                unsafe {
-                    let bytes: &[u8; $N] = mem::transmute(self);
-                    let len = (bytes[$N - 1] & ! 0x80) as usize;
-                    Some(str::from_utf8_unchecked(&bytes[0..len]))
-                }
-            }
-            #[cfg(target_endian = "big")]
-            fn get_packed_ref(&self) -> Option<&str> {
-                if ! self.is_inlined() { return None; }
-                unsafe {
-                    let bytes: &[u8; $N] = mem::transmute(self);
-                    let len = (bytes[0] & ! 0x80) as usize;
-                    match str::from_utf8_unchecked(&bytes[1..(len + 1)]) {
-                        Ok(s) => Some(s),
-                        Err(_) => None
-                    }
-                }
+impl<S, E> fmt::Debug for Sym<S, E>
+    where S: sym::Symbol + fmt::Debug + 'static,
+          S::Id: InlineId + fmt::Debug,
+          E: DebugPack<S::Id>
+{
+    /// Format an inlined symbol as `InlineSym(value)`, using the encoder to
+    /// decode its value.  Other symbols are formatted as `InlineSym(id =
+    /// value)` if a resolver for them is in scope (see
+    /// `symtern::fmt::with_resolver`), or as a plain struct otherwise.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let id = self.wrapped.id_ref();
+        if id.is_inlined() {
+            match E::with_debug_value(id, &mut |value| f.debug_tuple("InlineSym").field(value).finish()) {
+                Some(result) => result,
+                None => f.debug_tuple("InlineSym").field(id).finish(),
             }
+        } else {
+            crate::fmt::fmt_symbol(&self.wrapped, "InlineSym", id, f)
+                .unwrap_or_else(|| f.debug_struct("Sym").field("wrapped", &self.wrapped).finish())
         }
     }
 }
-impl_pack!(u16, 2);
-impl_pack!(u32, 4);
-impl_pack!(u64, 8);
-
-/// Symbol type used by the [`Inline`](struct.Inline.html) adaptor.
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
-pub struct Sym<S> {
-    wrapped: S
-}
 
-impl<S> sym::Symbol for Sym<S>
+impl<S, E> sym::Symbol for Sym<S, E>
     where S: sym::Symbol
 {
     type Id = S::Id;
@@ -127,18 +89,54 @@ impl<S> sym::Symbol for Sym<S>
 
     #[cfg(not(debug_assertions))]
     fn create(id: Self::Id) -> Self {
-        Sym{wrapped: <S as ::sym::Symbol>::create(id)}
+        <S as crate::sym::Symbol>::create(id).into()
     }
 
     #[cfg(debug_assertions)]
     fn create(id: Self::Id, pool_id: crate::sym::PoolId) -> Self {
-        Sym{wrapped: <S as crate::sym::Symbol>::create(id, pool_id)}
+        <S as crate::sym::Symbol>::create(id, pool_id).into()
     }
 }
 
-impl<S> From<S> for Sym<S> {
+impl<S, E> sym::Generational for Sym<S, E>
+    where S: sym::Generational
+{
+    fn generation(&self) -> sym::Generation { self.wrapped.generation() }
+
+    #[cfg(not(debug_assertions))]
+    fn create_with_generation(id: Self::Id, generation: sym::Generation) -> Self {
+        S::create_with_generation(id, generation).into()
+    }
+
+    #[cfg(debug_assertions)]
+    fn create_with_generation(id: Self::Id, generation: sym::Generation, pool_id: sym::PoolId) -> Self {
+        S::create_with_generation(id, generation, pool_id).into()
+    }
+}
+
+impl<S, E> From<S> for Sym<S, E> {
     fn from(s: S) -> Self {
-        Sym{wrapped: s}
+        Sym{wrapped: s, encoder: PhantomData}
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<S, E> serde::Serialize for Sym<S, E>
+    where S: sym::Symbol,
+          S::Id: serde::Serialize
+{
+    fn serialize<Z: serde::Serializer>(&self, serializer: Z) -> ::std::result::Result<Z::Ok, Z::Error> {
+        sym::serialize(self, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, S, E> serde::Deserialize<'de> for Sym<S, E>
+    where S: sym::Symbol,
+          S::Id: serde::Deserialize<'de>
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> ::std::result::Result<Self, D::Error> {
+        sym::deserialize(deserializer)
     }
 }
 
@@ -148,6 +146,11 @@ impl<S> From<S> for Sym<S> {
 /// inside the symbol*; strings of the same or greater size will be passed to
 /// the wrapped interner.
 ///
+/// Byte strings, `OsStr` and `Path` values are inlined the same way, when the
+/// wrapped pool stores that type; see [`Inlinable`].  Other value types can
+/// be inlined by supplying a custom [`Pack`] encoder as the second type
+/// parameter, `E`.
+///
 /// Simple benchmarks included with the crate indicate that this gives an
 /// approximately 6x (82%) speedup over the basic [`Pool`] for strings small
 /// enough to be inlined.
@@ -179,44 +182,106 @@ impl<S> From<S> for Sym<S> {
 /// assert_eq!(0, pool.len());
 /// ```
 ///
-/// The internal `Pack` trait, which provides the inlining functionality, is
-/// implemented for `u16`, `u32`, and `u64`; it will be implemented for `u128`
-/// as well when support for [128-bit integers] lands.
+/// The wrapped pool's ID type must implement [`InlineId`], which is
+/// implemented for `u16`, `u32`, `u64`, `u128` and their non-zero
+/// counterparts.  A pool with `u128` IDs inlines strings of up to 15 bytes,
+/// which covers most identifiers, at the cost of 16-byte symbols.
 ///
 /// [`Pool`]: ../struct.Pool.html
-/// [128-bit integers]: https://github.com/rust-lang/rfcs/blob/master/text/1504-int128.md
-#[derive(Copy, Clone, Debug)]
-pub struct Inline<W> {
-    wrapped: W
+/// [`Inlinable`]: trait.Inlinable.html
+/// [`Pack`]: trait.Pack.html
+/// [`InlineId`]: trait.InlineId.html
+#[derive(Clone, Debug)]
+pub struct Inline<W, E = Bytes> {
+    wrapped: W,
+    counts: InternCounts,
+    encoder: PhantomData<E>,
+}
+
+/// Number of successful interns served by each path, reported through
+/// `Stats`.  Atomic so that adaptors over pools interned through a shared
+/// reference remain `Sync`.
+#[derive(Debug, Default)]
+struct InternCounts {
+    inlined: AtomicUsize,
+    wrapped: AtomicUsize,
+}
+
+impl Clone for InternCounts {
+    fn clone(&self) -> Self {
+        InternCounts{inlined: AtomicUsize::new(self.inlined.load(Ordering::Relaxed)),
+                     wrapped: AtomicUsize::new(self.wrapped.load(Ordering::Relaxed))}
+    }
 }
 
-impl<W> Inline<W> {
+impl<W, E> Inline<W, E> {
     /// Create a new, empty symbol pool
     pub fn new() -> Self
         where W: Default
     {
         Default::default()
     }
+
+    /// Wrap an existing pool.  Unlike `From::from`, this works with any
+    /// encoder.
+    pub fn wrap(wrapped: W) -> Self {
+        Inline{wrapped, counts: Default::default(), encoder: PhantomData}
+    }
 }
 
-impl<W> Default for Inline<W>
+impl<W, E> Default for Inline<W, E>
     where W: Default
 {
     fn default() -> Self {
-        Inline{wrapped: Default::default()}
+        Inline::wrap(Default::default())
     }
 }
 
 
 impl<W> From<W> for Inline<W> {
     fn from(w: W) -> Self {
-        Inline{wrapped: w}
+        Inline::wrap(w)
+    }
+}
+
+/// An `Inline` adaptor is serialized as its wrapped pool; inlined values are
+/// stored only in their symbols.
+#[cfg(feature = "serde")]
+impl<W, E> serde::Serialize for Inline<W, E>
+    where W: serde::Serialize
+{
+    fn serialize<Z: serde::Serializer>(&self, serializer: Z) -> ::std::result::Result<Z::Ok, Z::Error> {
+        self.wrapped.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, W, E> serde::Deserialize<'de> for Inline<W, E>
+    where W: serde::Deserialize<'de>
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> ::std::result::Result<Self, D::Error> {
+        W::deserialize(deserializer).map(Inline::wrap)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<W, E> Inline<W, E>
+    where W: sym::Pool,
+          <<W as sym::Pool>::Symbol as sym::Symbol>::Id: InlineId
+{
+    /// Call `f`, binding any symbols it deserializes on the current thread to
+    /// this adaptor, and return its result.  See
+    /// [`Pool::deserialize_symbols`](../struct.Pool.html#method.deserialize_symbols).
+    pub fn deserialize_symbols<F, R>(&self, f: F) -> R
+        where F: FnOnce() -> R
+    {
+        sym::deserialize_symbols(sym::deserialization_id(self), f)
     }
 }
 
-impl<W> Len for Inline<W>
+impl<W, E> Len for Inline<W, E>
     where W: Len + crate::sym::Pool,
-          <<W as sym::Pool>::Symbol as sym::Symbol>::Id: Pack + ToPrimitive
+          <<W as sym::Pool>::Symbol as sym::Symbol>::Id: InlineId
 {
     /// Fetch the number of items contained in the pool.  The returned value
     /// does not count values inlined in symbols.
@@ -235,13 +300,70 @@ impl<W> Len for Inline<W>
     /// Check if the number of interned symbols has reached the maximum allowed
     /// for the pool's ID type.
     fn is_full(&self) -> bool {
-        (&self.wrapped).len() >= <<<W as sym::Pool>::Symbol as sym::Symbol>::Id as Pack>::msb_mask().to_usize().unwrap()
+        // A limit too large for `usize` can never be reached.
+        <<<W as sym::Pool>::Symbol as sym::Symbol>::Id as InlineId>::msb_mask().to_usize()
+            .is_some_and(|max| self.wrapped.len() >= max)
+    }
+}
+
+impl<W, E> Capacity for Inline<W, E>
+    where W: Capacity
+{
+    /// Fetch the number of values the wrapped pool can hold without
+    /// reallocating.  Values inlined in symbols do not use this space.
+    fn capacity(&self) -> usize {
+        self.wrapped.capacity()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.wrapped.reserve(additional)
+    }
+
+    fn try_reserve(&mut self, additional: usize) -> Result<()> {
+        self.wrapped.try_reserve(additional)
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.wrapped.shrink_to_fit()
     }
 }
 
-impl<W> crate::sym::Pool for Inline<W>
+impl<W, E> Clear for Inline<W, E>
+    where W: Clear
+{
+    /// Remove all values from the wrapped pool, keeping its allocated
+    /// capacity.  Inlined symbols remain resolvable.
+    fn clear(&mut self) {
+        self.wrapped.clear()
+    }
+}
+
+impl<W, E> Stats for Inline<W, E>
+    where W: Stats
+{
+    /// Fetch the wrapped pool's statistics, together with the number of
+    /// values interned inline and by the wrapped pool since the adaptor was
+    /// created.
+    fn stats(&self) -> PoolStats {
+        PoolStats{inlined_interns: self.counts.inlined.load(Ordering::Relaxed),
+                  wrapped_interns: self.counts.wrapped.load(Ordering::Relaxed),
+                  ..self.wrapped.stats()}
+    }
+}
+
+impl<W, E> DebugResolver for Inline<W, E>
+    where W: DebugResolver
+{
+    /// Only the wrapped pool's symbols need a resolver; inlined symbols are
+    /// formatted by their encoder.
+    fn with_debug_value(&self, symbol: &dyn Any, f: &mut dyn FnMut(&dyn fmt::Debug) -> fmt::Result) -> Option<fmt::Result> {
+        self.wrapped.with_debug_value(symbol, f)
+    }
+}
+
+impl<W, E> crate::sym::Pool for Inline<W, E>
     where W: sym::Pool,
-          <<W as sym::Pool>::Symbol as sym::Symbol>::Id: Pack,
+          <<W as sym::Pool>::Symbol as sym::Symbol>::Id: InlineId,
 {
     type Symbol = W::Symbol;
 
@@ -258,18 +380,23 @@ impl<W> crate::sym::Pool for Inline<W>
 
 macro_rules! impl_intern {
     ($($mutt: tt)*) => {
-        impl<'a, W, WS> Intern for &'a $($mutt)* Inline<W>
+        impl<'a, W, WS, T, E> Intern for &'a $($mutt)* Inline<W, E>
             where W: Len + sym::Pool<Symbol=WS>,
-                  &'a $($mutt)* W: Intern<Input=str,Symbol=<W as sym::Pool>::Symbol>,
+                  &'a $($mutt)* W: Intern<Input=T,Symbol=<W as sym::Pool>::Symbol>,
                   WS: sym::Symbol,
-                  WS::Id: Pack
+                  WS::Id: InlineId,
+                  T: ?Sized,
+                  E: Pack<T, WS::Id>
         {
-            type Input = str;
-            type Symbol = Sym<WS>;
+            type Input = T;
+            type Symbol = Sym<WS, E>;
 
             fn intern(self, s: &Self::Input) -> Result<Self::Symbol> {
-                match WS::Id::pack(s) {
-                    Some(id) => Ok(Sym{wrapped: self.wrapped.create_symbol(id)}),
+                match E::pack(s) {
+                    Some(id) => {
+                        self.counts.inlined.fetch_add(1, Ordering::Relaxed);
+                        Ok(self.wrapped.create_symbol(id).into())
+                    },
                     None => {
                         // since max capacity is changed by this adaptor, we
                         // need to do a capacity-check here.
@@ -277,7 +404,10 @@ macro_rules! impl_intern {
                             Err(ErrorKind::PoolOverflow.into())
                         } else {
                             match self.wrapped.intern(s) {
-                                Ok(b) => Ok(b.into()),
+                                Ok(b) => {
+                                    self.counts.wrapped.fetch_add(1, Ordering::Relaxed);
+                                    Ok(b.into())
+                                },
                                 Err(e) => Err(e)
                             }
                         }
@@ -291,29 +421,119 @@ impl_intern!();
 impl_intern!(mut);
 
 
-impl<'a, W, WS> Resolve for &'a Inline<W>
-    where for<'b> &'b W: Resolve<Input=WS, Output=&'b str>,
+impl<'a, W, WS, T, E> Lookup for &'a Inline<W, E>
+    where W: sym::Pool<Symbol=WS>,
+          &'a W: Lookup<Input=T,Symbol=WS>,
+          WS: sym::Symbol,
+          WS::Id: InlineId,
+          T: ?Sized,
+          E: Pack<T, WS::Id>
+{
+    type Input = T;
+    type Symbol = Sym<WS, E>;
+
+    /// Fetch the symbol for the given value, if it has been interned.
+    /// Values short enough to be inlined are always considered interned,
+    /// and are answered for without consulting the wrapped pool.
+    fn get(self, s: &Self::Input) -> Option<Self::Symbol> {
+        match E::pack(s) {
+            Some(id) => Some(self.wrapped.create_symbol(id).into()),
+            None => self.wrapped.get(s).map(From::from),
+        }
+    }
+
+    fn contains_symbol(self, symbol: Self::Symbol) -> bool {
+        symbol.id_ref().is_inlined() || self.wrapped.contains_symbol(symbol.wrapped)
+    }
+}
+
+
+impl<'a, W, WS, T, E> Resolve for &'a Inline<W, E>
+    where for<'b> &'b W: Resolve<Input=WS, Output=&'b T>,
           WS: 'a + sym::Symbol,
-          WS::Id: Pack + SymbolId,
+          WS::Id: InlineId,
+          T: 'a + ?Sized,
+          E: Pack<T, WS::Id>
 {
-    type Input = &'a Sym<WS>;
-    type Output = &'a str;
+    type Input = &'a Sym<WS, E>;
+    type Output = E::Unpacked<'a>;
 
+    /// Resolve the given symbol.  Fails with `ErrorKind::NoSuchSymbol` if the
+    /// symbol holds an inlined value that the encoder does not recognize.
     fn resolve(self, symbol: Self::Input) -> Result<Self::Output>
     {
-        match symbol.id_ref().get_packed_ref() {
-            Some(s) => Ok(s),
-            None => self.wrapped.resolve(symbol.wrapped)
+        if symbol.id_ref().is_inlined() {
+            E::unpack(symbol.id_ref()).ok_or_else(|| ErrorKind::NoSuchSymbol.into())
+        } else {
+            self.wrapped.resolve(symbol.wrapped).map(From::from)
         }
     }
 }
 
 
+impl<'a, W, WS, E> Remove for &'a mut Inline<W, E>
+    where &'a mut W: Remove<Symbol=WS>,
+          WS: sym::Symbol,
+          WS::Id: InlineId
+{
+    type Symbol = Sym<WS, E>;
+
+    /// Remove the given symbol's value from the wrapped pool.  Inlined
+    /// symbols have no stored value, so removing one always succeeds.
+    fn remove(self, symbol: Self::Symbol) -> Result<()> {
+        if symbol.id_ref().is_inlined() {
+            Ok(())
+        } else {
+            self.wrapped.remove(symbol.wrapped)
+        }
+    }
+}
+
+
+/// Iterator over the symbols and values stored by an
+/// [`Inline`](struct.Inline.html) adaptor's wrapped pool.
+pub struct Iter<It, E = Bytes> {
+    wrapped: It,
+    encoder: PhantomData<E>,
+}
+
+impl<It, WS, O, E> Iterator for Iter<It, E>
+    where It: Iterator<Item=(WS, O)>
+{
+    type Item = (Sym<WS, E>, O);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.wrapped.next().map(|(sym, value)| (sym.into(), value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.wrapped.size_hint()
+    }
+}
+
+/// Iterate over values stored in the wrapped pool.
+///
+/// Values short enough to be inlined are never stored anywhere but in their
+/// symbols, so they will **not** be produced by this iterator.
+impl<'a, W, WS, E> Iterate for &'a Inline<W, E>
+    where &'a W: Iterate<Symbol=WS>,
+          WS: sym::Symbol,
+{
+    type Symbol = Sym<WS, E>;
+    type Output = <&'a W as Iterate>::Output;
+    type Iter = Iter<<&'a W as Iterate>::Iter, E>;
+
+    fn iter(self) -> Self::Iter {
+        Iter{wrapped: self.wrapped.iter(), encoder: PhantomData}
+    }
+}
+
+
 #[cfg(test)]
 mod tests {
-    use super::{Inline, Pack};
+    use super::{Inline, InlineId};
     use crate::sym::Symbol;
-    use crate::traits::{Intern, Resolve, Len};
+    use crate::traits::{Intern, Lookup, Remove, Resolve, Iterate, Len, Capacity, Clear, Stats};
 
     /// Check that the pool's size is affected only by non-inlined values.
     #[test]
@@ -333,6 +553,217 @@ mod tests {
         assert_eq!(Ok("xy"), pool.resolve(&xy));
     }
 
+    /// Check that the adaptor works with a pool using a non-default hasher.
+    #[test]
+    fn works_with_custom_hasher() {
+        use std::collections::hash_map::RandomState;
+
+        let mut pool = Inline::from(crate::basic::Pool::<str,u32,RandomState>::with_hasher(RandomState::new()));
+        let x = pool.intern("x").expect("failed to intern short string");
+        let long = pool.intern("a longer string").expect("failed to intern long string");
+        assert_eq!(Ok("x"), pool.resolve(&x));
+        assert_eq!(Ok("a longer string"), pool.resolve(&long));
+        assert_eq!(1, pool.len());
+    }
+
+    /// Check that inlinable strings are found without touching the wrapped
+    /// pool, and that other strings are looked up in it.
+    #[test]
+    fn lookup_answers_inlined_values_directly() {
+        let mut pool = Inline::<crate::basic::Pool<str,u32>>::new();
+        let fn_sym = pool.get("fn").expect("failed to look up inlinable string");
+        assert!(fn_sym.id().is_inlined());
+        assert_eq!(Ok(fn_sym), pool.intern("fn"));
+        assert!(pool.contains_symbol(fn_sym));
+
+        assert_eq!(None, pool.get("a longer string"));
+        let long = pool.intern("a longer string").expect("failed to intern long string");
+        assert_eq!(Some(long), pool.get("a longer string"));
+        assert!(pool.contains_symbol(long));
+        assert_eq!(1, pool.len());
+    }
+
+    /// Check that iteration produces only values stored in the wrapped pool.
+    #[test]
+    fn iterates_over_non_inlined_values() {
+        let mut pool = Inline::<crate::basic::Pool<str,u32>>::new();
+        pool.intern("x").expect("failed to intern short string");
+        let long = pool.intern("a longer string").expect("failed to intern long string");
+
+        assert_eq!(vec![(long, "a longer string")], pool.iter().collect::<Vec<_>>());
+        assert_eq!(vec![long], pool.symbols().collect::<Vec<_>>());
+    }
+
+    /// Check that capacity management and clearing are forwarded to the
+    /// wrapped pool.
+    #[test]
+    fn forwards_capacity_to_wrapped_pool() {
+        let mut pool = Inline::from(crate::basic::Pool::<str,u32>::with_capacity(10));
+        assert!(pool.capacity() >= 10);
+        pool.reserve(100);
+        assert!(pool.capacity() >= 100);
+        pool.try_reserve(200).expect("failed to reserve space");
+        assert!(pool.capacity() >= 200);
+        pool.shrink_to_fit();
+        assert!(pool.capacity() < 200);
+
+        let x = pool.intern("x").expect("failed to intern short string");
+        pool.intern("longer").expect("failed to intern long string");
+        pool.clear();
+        assert!(pool.is_empty());
+        assert_eq!(Ok("x"), pool.resolve(&x));
+    }
+
+    /// Check that both inlined and wrapped symbols survive a round trip
+    /// through serialization together with their pool.
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        extern crate serde_json;
+        type P = Inline<crate::basic::Pool<str,u32>>;
+
+        let mut pool = P::new();
+        let syms = vec![pool.intern("ab").expect("failed to intern short string"),
+                        pool.intern("a longer string").expect("failed to intern long string")];
+
+        let pool_json = serde_json::to_string(&pool).expect("failed to serialize pool");
+        let syms_json = serde_json::to_string(&syms).expect("failed to serialize symbols");
+        let pool2: P = serde_json::from_str(&pool_json).expect("failed to deserialize pool");
+        let syms2: Vec<super::Sym<crate::basic::Sym<u32>>> =
+            pool2.deserialize_symbols(|| serde_json::from_str(&syms_json)).expect("failed to deserialize symbols");
+
+        assert_eq!(syms.iter().map(Symbol::id).collect::<Vec<_>>(),
+                   syms2.iter().map(Symbol::id).collect::<Vec<_>>());
+        assert_eq!(Ok("ab"), pool2.resolve(&syms2[0]));
+        assert_eq!(Ok("a longer string"), pool2.resolve(&syms2[1]));
+    }
+
+    /// Check that generation-checked symbols keep working through the
+    /// adaptor.
+    #[test]
+    fn wraps_recycling_pool() {
+        let mut pool = Inline::<crate::RecyclingPool<str,u32>>::new();
+        let short = pool.intern("x").expect("failed to intern short string");
+        let long = pool.intern("a longer string").expect("failed to intern long string");
+        pool.remove(short).expect("failed to remove inlined symbol");
+        pool.remove(long).expect("failed to remove value");
+        assert_eq!(Ok("x"), pool.resolve(&short));
+        assert_eq!(crate::ErrorKind::StaleSymbol, pool.resolve(&long).unwrap_err().kind());
+        assert!(pool.is_empty());
+    }
+
+    /// Check that stats report the wrapped pool's contents and count interns
+    /// by the path that served them.
+    #[test]
+    fn stats_count_inlined_interns() {
+        let mut pool = Inline::<crate::basic::Pool<str,u32>>::new();
+        pool.intern("abc").expect("failed to intern short string");
+        pool.intern("abc").expect("failed to intern short string");
+        pool.intern("a longer string").expect("failed to intern long string");
+        let stats = pool.stats();
+        assert_eq!((1, 15), (stats.len, stats.data_bytes));
+        assert_eq!((2, 1), (stats.inlined_interns, stats.wrapped_interns));
+    }
+
+    /// Check that `u128` IDs inline strings of up to 15 bytes.
+    #[test]
+    fn packs_u128_ids() {
+        let mut pool = Inline::<crate::basic::Pool<str,u128>>::new();
+        let fifteen = pool.intern("into_iter_mut_x").expect("failed to intern 15-byte string");
+        let sixteen = pool.intern("into_iter_mut_xy").expect("failed to intern 16-byte string");
+        assert!(fifteen.id().is_inlined());
+        assert!(! sixteen.id().is_inlined());
+        assert_eq!(Ok("into_iter_mut_x"), pool.resolve(&fifteen));
+        assert_eq!(Ok("into_iter_mut_xy"), pool.resolve(&sixteen));
+        assert_eq!(1, pool.len());
+    }
+
+    /// Check that byte strings, `OsStr` and `Path` values are inlined or
+    /// passed to the wrapped pool just like strings.
+    #[test]
+    fn inlines_other_input_types() {
+        use std::ffi::OsStr;
+        use std::path::Path;
+
+        let mut pool = Inline::<crate::basic::Pool<[u8],u32>>::new();
+        let short = pool.intern(&b"\xff\x00"[..]).expect("failed to intern short byte string");
+        let long = pool.intern(&b"\xff\x00\xff\x00"[..]).expect("failed to intern long byte string");
+        assert!(short.id().is_inlined());
+        assert_eq!(Ok(&b"\xff\x00"[..]), pool.resolve(&short));
+        assert_eq!(Ok(&b"\xff\x00\xff\x00"[..]), pool.resolve(&long));
+        assert_eq!(Some(short), pool.get(&b"\xff\x00"[..]));
+        assert_eq!(1, pool.len());
+
+        let mut pool = Inline::<crate::basic::Pool<OsStr,u32>>::new();
+        let short = pool.intern(OsStr::new("abc")).expect("failed to intern short OsStr");
+        let long = pool.intern(OsStr::new("a longer value")).expect("failed to intern long OsStr");
+        assert!(short.id().is_inlined());
+        assert_eq!(Ok(OsStr::new("abc")), pool.resolve(&short));
+        assert_eq!(Ok(OsStr::new("a longer value")), pool.resolve(&long));
+
+        let mut pool = Inline::<crate::basic::Pool<Path,u64>>::new();
+        let short = pool.intern(Path::new("src/a")).expect("failed to intern short path");
+        let long = pool.intern(Path::new("src/adaptors/inline.rs")).expect("failed to intern long path");
+        assert!(short.id().is_inlined());
+        assert_eq!(Ok(Path::new("src/a")), pool.resolve(&short));
+        assert_eq!(Ok(Path::new("src/adaptors/inline.rs")), pool.resolve(&long));
+        assert_eq!(1, pool.len());
+    }
+
+    /// Check that an inlined byte string that is not valid UTF-8 is rejected
+    /// by a string adaptor rather than resolved.
+    #[test]
+    fn rejects_inlined_values_of_other_types() {
+        let mut bytes = Inline::<crate::basic::Pool<[u8],u32>>::new();
+        let strings = Inline::<crate::basic::Pool<str,u32>>::new();
+        let sym = bytes.intern(&b"\xff"[..]).expect("failed to intern byte string");
+        assert_eq!("InlineSym([255])", format!("{:?}", sym));
+        assert_eq!(crate::ErrorKind::NoSuchSymbol, strings.resolve(&sym).unwrap_err().kind());
+    }
+
+    /// Check that an adaptor using the `Alphabet6` encoder inlines longer
+    /// identifiers, and passes other strings to the wrapped pool.
+    #[test]
+    fn packs_identifiers_with_alphabet6() {
+        use crate::adaptors::Alphabet6;
+
+        let mut pool = Inline::<crate::basic::Pool<str,u64>, Alphabet6>::new();
+        let ident = pool.intern("into_iter").expect("failed to intern identifier");
+        let other = pool.intern("a b").expect("failed to intern string");
+        assert!(ident.id().is_inlined());
+        assert!(! other.id().is_inlined());
+        assert_eq!(Ok("into_iter"), pool.resolve(&ident).as_deref());
+        assert_eq!(Ok("a b"), pool.resolve(&other).as_deref());
+        assert_eq!(Some(ident), pool.get("into_iter"));
+        assert_eq!(1, pool.len());
+        assert_eq!("InlineSym(\"into_iter\")", format!("{:?}", ident));
+    }
+
+    /// Check that inlining works with non-zero IDs, and that the symbols
+    /// keep their niche.
+    #[test]
+    fn packs_nonzero_ids() {
+        use std::mem::size_of;
+        use std::num::NonZeroU32;
+
+        let mut pool = Inline::<crate::basic::Pool<str,NonZeroU32>>::new();
+        let short = pool.intern("abc").expect("failed to intern short string");
+        let long = pool.intern("a longer string").expect("failed to intern long string");
+        assert!(short.id().is_inlined());
+        assert!(! long.id().is_inlined());
+        assert_eq!(Ok("abc"), pool.resolve(&short));
+        assert_eq!(Ok("a longer string"), pool.resolve(&long));
+        assert_eq!(1, pool.len());
+
+        assert_eq!(size_of::<super::Sym<crate::basic::Sym<NonZeroU32>>>(),
+                   size_of::<Option<super::Sym<crate::basic::Sym<NonZeroU32>>>>());
+        #[cfg(not(debug_assertions))]
+        assert_eq!(4, size_of::<Option<super::Sym<crate::basic::Sym<NonZeroU32>>>>());
+        #[cfg(debug_assertions)]
+        assert_eq!(size_of::<(u32, crate::sym::PoolId)>(),
+                   size_of::<Option<super::Sym<crate::basic::Sym<NonZeroU32>>>>());
+    }
+
     /// Check that we can stack Inline adaptors and still resolve through
     /// them.  This is a compile-time check:  we're verifying that the Resolve
     /// implementation works whether the wrapped pool takes its `resolve`
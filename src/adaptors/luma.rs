@@ -7,18 +7,41 @@
 // distributed except according to those terms.
 //! "Lifetime-safe" interner adaptor.
 // [Module documentation lives on the exported adaptor, `Luma`.]
+use std::any::Any;
+use std::borrow::Borrow;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
 use std::marker::PhantomData;
 use std::cell::{RefCell, Ref};
+use std::vec;
 
-use crate::{sym, traits, Result};
+use crate::{sym, traits, Result, AppendPool};
+use crate::fmt::DebugResolver;
 
 /// Symbol type used by the [`Luma`](struct.Luma.html) adaptor.
-#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Sym<'a, W> {
     wrapped: W,
     lifetime: PhantomData<&'a ()>,
 }
 
+impl<'a, W> fmt::Debug for Sym<'a, W>
+    where W: sym::Symbol + fmt::Debug + 'static,
+          W::Id: fmt::Debug
+{
+    /// Format the symbol as `LumaSym(id = value)` if a resolver for it is in
+    /// scope (see `symtern::fmt::with_resolver`), or as a plain struct
+    /// otherwise.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        crate::fmt::fmt_symbol(&self.wrapped, "LumaSym", self.wrapped.id_ref(), f).unwrap_or_else(|| {
+            f.debug_struct("Sym")
+                .field("wrapped", &self.wrapped)
+                .field("lifetime", &self.lifetime)
+                .finish()
+        })
+    }
+}
+
 impl<'a,W> sym::Symbol for Sym<'a, W>
     where W: sym::Symbol {
     type Id = W::Id;
@@ -50,12 +73,51 @@ impl<'a,W> sym::Symbol for Sym<'a, W>
     }
 }
 
+impl<'a,W> sym::Generational for Sym<'a, W>
+    where W: sym::Generational {
+    fn generation(&self) -> sym::Generation {
+        self.wrapped.generation()
+    }
+
+    #[cfg(debug_assertions)]
+    fn create_with_generation(id: Self::Id, generation: sym::Generation, pool_id: sym::PoolId) -> Self {
+        Sym{wrapped: W::create_with_generation(id, generation, pool_id),
+            lifetime: PhantomData}
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn create_with_generation(id: Self::Id, generation: sym::Generation) -> Self {
+        Sym{wrapped: W::create_with_generation(id, generation),
+            lifetime: PhantomData}
+    }
+}
+
 impl<'a,W> From<W> for Sym<'a, W> {
     fn from(w: W) -> Self {
         Sym{wrapped: w, lifetime: PhantomData}
     }
 }
 
+#[cfg(feature = "serde")]
+impl<'a, W> serde::Serialize for Sym<'a, W>
+    where W: sym::Symbol,
+          W::Id: serde::Serialize
+{
+    fn serialize<Z: serde::Serializer>(&self, serializer: Z) -> ::std::result::Result<Z::Ok, Z::Error> {
+        sym::serialize(self, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'a, 'de, W> serde::Deserialize<'de> for Sym<'a, W>
+    where W: sym::Symbol,
+          W::Id: serde::Deserialize<'de>
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> ::std::result::Result<Self, D::Error> {
+        sym::deserialize(deserializer)
+    }
+}
+
 /// "Lifetime-safe" interner adaptor.
 ///
 /// This adaptor's symbols are treated as references to their source pool
@@ -94,6 +156,25 @@ impl<'a,W> From<W> for Sym<'a, W> {
 ///
 /// let _ = pool.intern("bar").expect("failed to intern a value"); //~ PANIC already borrowed: BorrowMutError
 /// ```
+///
+/// Wrapping an [`AppendPool`](../struct.AppendPool.html) avoids this panic:
+/// an append-only pool interns through a shared reference, so `Luma` never
+/// needs to borrow it mutably, and resolved values can be held while new
+/// values are interned.
+///
+/// ```rust
+/// use symtern::prelude::*;
+/// use symtern::AppendPool;
+/// use symtern::adaptors::Luma;
+///
+/// let pool = Luma::from(AppendPool::<str, u32>::new());
+/// let x = pool.intern("foo").expect("failed to intern a value");
+/// let foo = pool.resolve(x).expect("failed to resolve the value we just interned");
+///
+/// let y = pool.intern("bar").expect("failed to intern a value");
+/// assert_eq!("foo", &*foo);
+/// assert_eq!("bar", &*pool.resolve(y).expect("failed to resolve a value"));
+/// ```
 #[derive(Default)]
 pub struct Luma<W> {
     wrapped: RefCell<W>
@@ -114,6 +195,38 @@ impl<W> From<W> for Luma<W> {
     }
 }
 
+/// A `Luma` adaptor is serialized as its wrapped pool.
+#[cfg(feature = "serde")]
+impl<W> serde::Serialize for Luma<W>
+    where W: serde::Serialize
+{
+    fn serialize<Z: serde::Serializer>(&self, serializer: Z) -> ::std::result::Result<Z::Ok, Z::Error> {
+        self.wrapped.borrow().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, W> serde::Deserialize<'de> for Luma<W>
+    where W: serde::Deserialize<'de>
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> ::std::result::Result<Self, D::Error> {
+        W::deserialize(deserializer).map(Luma::from)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<W: sym::Pool> Luma<W> {
+    /// Call `f`, binding any symbols it deserializes on the current thread to
+    /// this adaptor, and return its result.  See
+    /// [`Pool::deserialize_symbols`](../struct.Pool.html#method.deserialize_symbols).
+    pub fn deserialize_symbols<F, R>(&self, f: F) -> R
+        where F: FnOnce() -> R
+    {
+        let pool_id = sym::deserialization_id(&*self.wrapped.borrow());
+        sym::deserialize_symbols(pool_id, f)
+    }
+}
+
 impl<'a, W, BS, BI: ?Sized> traits::Intern for &'a Luma<W>
     where for<'b> &'b mut W: traits::Intern<Symbol=BS, Input=BI>,
           BS: sym::Symbol + traits::Symbol
@@ -127,6 +240,41 @@ impl<'a, W, BS, BI: ?Sized> traits::Intern for &'a Luma<W>
     }
 }
 
+/// An `AppendPool` interns through a shared reference, so a `Luma` wrapping
+/// one never borrows it mutably to intern a value, and interning can never
+/// conflict with a value still borrowed from `resolve`.
+impl<'a, T: ?Sized, I, S> traits::Intern for &'a Luma<AppendPool<T, I, S>>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: traits::SymbolId,
+          S: BuildHasher
+{
+    type Input = T;
+    type Symbol = Sym<'a, crate::basic::Sym<I>>;
+
+    fn intern(self, input: &Self::Input) -> Result<Self::Symbol> {
+        let inner_result = self.wrapped.borrow().intern(input);
+        inner_result.map(From::from)
+    }
+}
+
+impl<'a, W, BS, BI: ?Sized> traits::Lookup for &'a Luma<W>
+    where for<'b> &'b W: traits::Lookup<Symbol=BS, Input=BI>,
+          BS: sym::Symbol + traits::Symbol
+{
+    type Input = BI;
+    type Symbol = Sym<'a,BS>;
+
+    fn get(self, input: &Self::Input) -> Option<Self::Symbol> {
+        let inner_result = self.wrapped.borrow().get(input);
+        inner_result.map(From::from)
+    }
+
+    fn contains_symbol(self, sym: Self::Symbol) -> bool {
+        self.wrapped.borrow().contains_symbol(sym.wrapped)
+    }
+}
+
 impl<'a, W, BI, BO: ?Sized> traits::Resolve for &'a Luma<W>
     where for<'b> &'b W: traits::Resolve<Input=BI, Output=&'b BO>,
           BI: sym::Symbol + traits::Symbol,
@@ -139,6 +287,66 @@ impl<'a, W, BI, BO: ?Sized> traits::Resolve for &'a Luma<W>
     }
 }
 
+/// Iterator over the symbols and values stored in a
+/// [`Luma`](struct.Luma.html)-wrapped pool.
+///
+/// The set of symbols to visit is fixed when the iterator is created; values
+/// interned while iterating will not be produced.
+pub struct Iter<'a, W: 'a, BS> {
+    luma: &'a Luma<W>,
+    symbols: vec::IntoIter<BS>,
+}
+
+impl<'a, W, BS, BO: ?Sized> Iterator for Iter<'a, W, BS>
+    where for<'b> &'b W: traits::Resolve<Input=BS, Output=&'b BO>,
+          BS: sym::Symbol + traits::Symbol,
+          BO: 'a
+{
+    type Item = (Sym<'a, BS>, Ref<'a, BO>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let luma = self.luma;
+        self.symbols.next().map(|s| {
+            let sym = Sym::from(s);
+            (sym, traits::Resolve::resolve(luma, sym).unwrap())
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.symbols.size_hint()
+    }
+}
+
+impl<'a, W, BS, BO: ?Sized> traits::Iterate for &'a Luma<W>
+    where for<'b> &'b W: traits::Iterate<Symbol=BS> + traits::Resolve<Input=BS, Output=&'b BO>,
+          BS: sym::Symbol + traits::Symbol,
+          BO: 'a
+{
+    type Symbol = Sym<'a, BS>;
+    type Output = Ref<'a, BO>;
+    type Iter = Iter<'a, W, BS>;
+
+    fn iter(self) -> Self::Iter {
+        let symbols: Vec<BS> = self.wrapped.borrow().symbols().collect();
+        Iter{luma: self, symbols: symbols.into_iter()}
+    }
+}
+
+impl<W> traits::Stats for Luma<W> where W: traits::Stats {
+    fn stats(&self) -> traits::PoolStats {
+        self.wrapped.borrow().stats()
+    }
+}
+
+impl<W> DebugResolver for Luma<W> where W: DebugResolver {
+    fn with_debug_value(&self, symbol: &dyn Any, f: &mut dyn FnMut(&dyn fmt::Debug) -> fmt::Result) -> Option<fmt::Result> {
+        // Resolving is impossible while the wrapped pool is borrowed mutably,
+        // which only happens if a value is being interned.
+        let wrapped = self.wrapped.try_borrow().ok()?;
+        wrapped.with_debug_value(symbol, f)
+    }
+}
+
 impl<W> traits::Len for Luma<W> where W: traits::Len {
     fn len(&self) -> usize {
         self.wrapped.borrow().len()
@@ -151,7 +359,27 @@ impl<W> traits::Len for Luma<W> where W: traits::Len {
     }
 }
 
-    
+impl<W> traits::Capacity for Luma<W> where W: traits::Capacity {
+    fn capacity(&self) -> usize {
+        self.wrapped.borrow().capacity()
+    }
+    fn reserve(&mut self, additional: usize) {
+        self.wrapped.get_mut().reserve(additional)
+    }
+    fn try_reserve(&mut self, additional: usize) -> Result<()> {
+        self.wrapped.get_mut().try_reserve(additional)
+    }
+    fn shrink_to_fit(&mut self) {
+        self.wrapped.get_mut().shrink_to_fit()
+    }
+}
+
+impl<W> traits::Clear for Luma<W> where W: traits::Clear {
+    fn clear(&mut self) {
+        self.wrapped.get_mut().clear()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::prelude::*;
@@ -169,4 +397,91 @@ mod tests {
         assert_eq!(0u64, *luma.resolve(a).unwrap());
         assert_eq!(1u64, *luma.resolve(b).unwrap());
     }
+
+    /// Check that a `Luma` over an append-only pool can intern while a
+    /// resolved value is still borrowed.
+    #[test]
+    fn interns_into_append_pool_while_resolved_value_is_borrowed() {
+        let luma = Luma::from(crate::AppendPool::<str, u32>::new());
+        let a = luma.intern("a").expect("failed to intern value");
+        let value = luma.resolve(a).expect("failed to resolve value");
+        let b = luma.intern("b").expect("failed to intern value");
+        assert_eq!("a", &*value);
+        assert_eq!("b", &*luma.resolve(b).expect("failed to resolve value"));
+        assert_eq!(2, luma.len());
+    }
+
+    /// Check that values can be looked up without interning them.
+    #[test]
+    fn lookup_does_not_intern() {
+        let luma = Luma::from(Pool::<str, u32>::new());
+        assert_eq!(None, luma.get("a"));
+        assert!(luma.is_empty());
+
+        let a = luma.intern("a").expect("failed to intern value");
+        assert_eq!(Some(a), luma.get("a"));
+        assert!(luma.contains_symbol(a));
+        assert_eq!(1, luma.len());
+    }
+
+    /// Check that iteration visits every value in the wrapped pool.
+    #[test]
+    fn iterates_over_values() {
+        let luma = Luma::from(Pool::<str, u32>::new());
+        let a = luma.intern("a").expect("failed to intern value");
+        let b = luma.intern("b").expect("failed to intern value");
+
+        assert_eq!(vec![a, b], luma.symbols().collect::<Vec<_>>());
+        assert_eq!(vec!["a", "b"], luma.values().map(|v| v.to_string()).collect::<Vec<_>>());
+    }
+
+    /// Check that capacity management is forwarded to the wrapped pool.
+    #[test]
+    fn forwards_capacity_to_wrapped_pool() {
+        let mut luma = Luma::from(Pool::<str, u32>::with_capacity(10));
+        assert!(luma.capacity() >= 10);
+        luma.reserve(100);
+        assert!(luma.capacity() >= 100);
+        luma.try_reserve(200).expect("failed to reserve space");
+        assert!(luma.capacity() >= 200);
+        luma.shrink_to_fit();
+        assert!(luma.capacity() < 200);
+    }
+
+    /// Check that clearing is forwarded to the wrapped pool.
+    #[test]
+    fn can_clear_wrapped_pool() {
+        let mut luma = Luma::from(Pool::<str, u32>::with_capacity(10));
+        let capacity = {
+            let _ = luma.intern("a").expect("failed to intern value");
+            luma.capacity()
+        };
+        luma.clear();
+        assert!(luma.is_empty());
+        assert_eq!(capacity, luma.capacity());
+        let b = luma.intern("b").expect("failed to intern value");
+        assert_eq!("b", &*luma.resolve(b).unwrap());
+    }
+
+    /// Check that stats are fetched from the wrapped pool.
+    #[test]
+    fn forwards_stats_to_wrapped_pool() {
+        let luma = Luma::from(Pool::<str, u32>::new());
+        let _ = luma.intern("abc").expect("failed to intern value");
+        let stats = luma.stats();
+        assert_eq!((1, 3), (stats.len, stats.data_bytes));
+    }
+
+    /// Check that the adaptor works with a pool using a non-default hasher.
+    #[test]
+    fn works_with_custom_hasher() {
+        use std::collections::hash_map::RandomState;
+
+        let luma = Luma::from(Pool::<str, u32, RandomState>::with_hasher(RandomState::new()));
+        let a = luma.intern("a").expect("failed to intern value");
+        let b = luma.intern("b").expect("failed to intern value");
+        assert!(a != b);
+        assert_eq!("a", &*luma.resolve(a).unwrap());
+        assert_eq!("b", &*luma.resolve(b).unwrap());
+    }
 }
@@ -49,7 +49,10 @@
 //! under a certain length, this adaptor will store them directly in the
 //! returned symbols &mdash; entirely bypassing the wrapped interner.  If you
 //! expect to be working with many short strings, it may perform better than
-//! the basic interner.
+//! the basic interner.  Pools of byte strings, `OsStr` or `Path` values can
+//! be wrapped the same way, and a custom [`Pack`] encoder can inline other
+//! small value types.  The [`Alphabet6`] encoder packs identifier-like
+//! strings at six bits per character, inlining up to ten of them in a `u64`.
 //!
 //! ```rust file="examples/combining-adaptors.rs" id="inline"
 //! use symtern::prelude::*;
@@ -94,14 +97,46 @@
 //! //` }
 //! ```
 //!
+//! ## Refcounted
+//!
+//! The [`Refcounted`] adaptor, and its thread-safe counterpart
+//! [`SyncRefcounted`], return reference-counted handles instead of plain
+//! symbols.  When the last handle for a value is dropped, the value is removed
+//! from the wrapped pool &mdash; which must implement [`Remove`], as
+//! [`RecyclingPool`] does.
+//!
+//! ```rust
+//! use symtern::prelude::*;
+//! use symtern::RecyclingPool;
+//! use symtern::adaptors::Refcounted;
+//!
+//! let pool = Refcounted::<RecyclingPool<str, u32>>::new();
+//! {
+//!     let _tmp = pool.intern("temporary").expect("interning failed");
+//!     assert_eq!(1, pool.len());
+//! }
+//! assert!(pool.is_empty());
+//! ```
+//!
 //! [`Luma`]: struct.Luma.html
 //! [`Inline`]: struct.Inline.html
+//! [`Pack`]: trait.Pack.html
+//! [`Alphabet6`]: struct.Alphabet6.html
+//! [`Refcounted`]: struct.Refcounted.html
+//! [`SyncRefcounted`]: struct.SyncRefcounted.html
+//! [`Remove`]: ../traits/trait.Remove.html
+//! [`RecyclingPool`]: ../struct.RecyclingPool.html
 
 mod inline;
+mod pack;
+pub mod testkit;
 mod luma;
+mod refcount;
 
-pub use self::inline::{Inline, Sym as InlineSym};
-pub use self::luma::{Luma, Sym as LumaSym};
+pub use self::inline::{Inline, Sym as InlineSym, Iter as InlineIter};
+pub use self::pack::{Pack, DebugPack, Bytes, Alphabet6, InlineId, Inlinable};
+pub use self::luma::{Luma, Sym as LumaSym, Iter as LumaIter};
+pub use self::refcount::{Refcounted, RcSym, SyncRefcounted, ArcSym};
 
 #[cfg(all(feature = "composition-tests", test))]
 mod tests {
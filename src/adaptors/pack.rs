@@ -0,0 +1,523 @@
+// Copyright (C) 2016-2017 Symtern Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-Apache
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or
+// distributed except according to those terms.
+//! Encodings used by the `Inline` adaptor to store values inside symbol IDs.
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::fmt;
+use std::num::{NonZeroU16, NonZeroU32, NonZeroU64};
+use std::ops::Deref;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::str;
+
+use crate::traits::SymbolId;
+
+/// Symbol-ID types that can hold inlined values.
+///
+/// An ID with its most-significant bit set holds an inlined value; all other
+/// IDs are assigned by the wrapped pool, which the [`Inline`] adaptor keeps
+/// from growing large enough to use that bit.
+///
+/// Implemented for `u16`, `u32`, `u64`, `u128`, `NonZeroU16`, `NonZeroU32` and
+/// `NonZeroU64`.
+///
+/// [`Inline`]: struct.Inline.html
+pub trait InlineId: SymbolId + Ord {
+    /// Number of bits below the most-significant bit, which are available to
+    /// hold an inlined value.
+    const PAYLOAD_BITS: u32;
+
+    /// Get a mask for the most-significant bit in the implementor.
+    fn msb_mask() -> Self;
+
+    /// Create an inlined ID holding the given bits below its most-significant
+    /// bit, or `None` if `payload` has more than `PAYLOAD_BITS` significant
+    /// bits.
+    fn from_payload(payload: u128) -> Option<Self>;
+
+    /// Fetch the bits below the ID's most-significant bit.
+    fn payload(&self) -> u128;
+
+    /// Check if the ID holds an inlined value.
+    fn is_inlined(&self) -> bool {
+        *self >= Self::msb_mask()
+    }
+
+    /// Pack a byte slice _shorter_ than the ID type into an ID, storing the
+    /// slice's length in the most-significant byte; returns `None` if the
+    /// slice is too long.  This is the encoding used by [`Bytes`], and may be
+    /// used by other encoders as well.
+    ///
+    /// [`Bytes`]: struct.Bytes.html
+    fn pack_bytes(bytes: &[u8]) -> Option<Self>;
+
+    /// Fetch the bytes stored in the ID by `pack_bytes`, or `None` if the ID
+    /// does not hold any.
+    fn packed_bytes(&self) -> Option<&[u8]>;
+}
+
+/// Create a mask value for the most significant _bit_ in an $N-_byte_
+/// unsigned integer.
+macro_rules! msb_mask {
+    ($T: tt, $N: expr) => ( (1 as $T) << ($N * 8 - 1) );
+}
+
+#[test]
+fn test_msb_mask() {
+    assert_eq!(1 << 7, msb_mask!(u8, 1));
+    assert_eq!(1 << 15, msb_mask!(u16, 2));
+    assert_eq!(1 << 31, msb_mask!(u32, 4));
+    assert_eq!(1u64 << 63, msb_mask!(u64, 8));
+    assert_eq!(1u128 << 127, msb_mask!(u128, 16));
+}
+
+macro_rules! impl_inline_id {
+    ($T: tt, $N: expr) => {
+        impl InlineId for $T {
+            const PAYLOAD_BITS: u32 = $N * 8 - 1;
+
+            fn msb_mask() -> Self {
+                msb_mask!($T, $N)
+            }
+
+            fn from_payload(payload: u128) -> Option<Self> {
+                if payload >> Self::PAYLOAD_BITS != 0 { return None; }
+                Some(payload as $T | msb_mask!($T, $N))
+            }
+
+            fn payload(&self) -> u128 {
+                (*self & ! msb_mask!($T, $N)) as u128
+            }
+
+            #[cfg(target_endian = "little")]
+            fn pack_bytes(s: &[u8]) -> Option<Self> {
+                if s.len() >= $N { return None; }
+
+                let mut bytes = [0u8; $N];
+                bytes[0..s.len()].copy_from_slice(s);
+                bytes[$N - 1] = s.len() as u8 | 0x80;
+
+                Some($T::from_ne_bytes(bytes))
+            }
+            #[cfg(target_endian = "big")]
+            fn pack_bytes(s: &[u8]) -> Option<Self> {
+                if s.len() >= $N { return None; }
+
+                let mut bytes = [0u8; $N];
+                bytes[1..(s.len() + 1)].copy_from_slice(s);
+                bytes[0] = s.len() as u8 | 0x80;
+
+                Some($T::from_ne_bytes(bytes))
+            }
+
+            #[cfg(target_endian = "little")]
+            fn packed_bytes(&self) -> Option<&[u8]> {
+                if ! self.is_inlined() { return None; }
+                let bytes: &[u8; $N] = unsafe { &*(self as *const $T as *const [u8; $N]) };
+                let len = (bytes[$N - 1] & ! 0x80) as usize;
+                bytes.get(0..len)
+            }
+            #[cfg(target_endian = "big")]
+            fn packed_bytes(&self) -> Option<&[u8]> {
+                if ! self.is_inlined() { return None; }
+                let bytes: &[u8; $N] = unsafe { &*(self as *const $T as *const [u8; $N]) };
+                let len = (bytes[0] & ! 0x80) as usize;
+                bytes.get(1..(len + 1))
+            }
+        }
+    }
+}
+impl_inline_id!(u16, 2);
+impl_inline_id!(u32, 4);
+impl_inline_id!(u64, 8);
+impl_inline_id!(u128, 16);
+
+/// Non-zero IDs are packed exactly like the plain integers they store; since
+/// a packed value always has its most-significant bit set, it is never zero.
+macro_rules! impl_nonzero_inline_id {
+    ($($N: ty: $U: ty),*) => {$(
+        impl InlineId for $N {
+            const PAYLOAD_BITS: u32 = <$U as InlineId>::PAYLOAD_BITS;
+
+            fn msb_mask() -> Self {
+                <$N>::new(<$U as InlineId>::msb_mask()).expect("Unexpected zero MSB mask")
+            }
+
+            fn from_payload(payload: u128) -> Option<Self> {
+                <$U as InlineId>::from_payload(payload).map(|id| <$N>::new(id).expect("Unexpected zero inlined ID"))
+            }
+
+            fn payload(&self) -> u128 {
+                self.get().payload()
+            }
+
+            fn pack_bytes(bytes: &[u8]) -> Option<Self> {
+                <$U as InlineId>::pack_bytes(bytes).map(|packed| <$N>::new(packed).expect("Unexpected zero packed value"))
+            }
+
+            fn packed_bytes(&self) -> Option<&[u8]> {
+                // Non-zero integers have the same layout as their plain
+                // counterparts.
+                let raw: &$U = unsafe { &*(self as *const $N as *const $U) };
+                raw.packed_bytes()
+            }
+        }
+    )*}
+}
+impl_nonzero_inline_id!(NonZeroU16: u16, NonZeroU32: u32, NonZeroU64: u64);
+
+
+/// Encoder that stores values of type `T` inside symbol IDs of type `I`, for
+/// use by the [`Inline`] adaptor.
+///
+/// Encoders are types used only as `Inline`'s second type parameter, and are
+/// never instantiated.  The default encoder, [`Bytes`], handles strings and
+/// other [`Inlinable`] types; other encoders can inline any value type the
+/// wrapped pool stores.  An implementation must uphold these rules, which can
+/// be checked with [`testkit::check_pack`]:
+///
+///   * Every ID returned by `pack` must have its most-significant bit set
+///     (see [`InlineId::is_inlined`]), so that it can never be mistaken for
+///     an ID assigned by the wrapped pool.
+///
+///   * Distinct values must be packed into distinct IDs, and `unpack` must
+///     return a value equal to the one that was packed.
+///
+/// `unpack` may also be handed IDs packed by a different encoder, and should
+/// return `None` for any it does not recognize.  `Inline` resolves symbols
+/// to the encoder's `Unpacked` type, which for most encoders is a reference
+/// borrowed from the ID itself or from static data; an encoder that must
+/// rebuild values from their IDs can use an owned type, or a `Cow`.  The
+/// adaptor's symbols implement `Debug` only if the encoder also implements
+/// [`DebugPack`].
+///
+/// For example, this encoder inlines small integers, resolving them through a
+/// static table:
+///
+/// ```rust
+/// use symtern::prelude::*;
+/// use symtern::Pool;
+/// use symtern::adaptors::{Inline, InlineId, Pack};
+///
+/// static SMALL: [u64; 256] = {
+///     let mut table = [0; 256];
+///     let mut i = 0;
+///     while i < 256 { table[i] = i as u64; i += 1; }
+///     table
+/// };
+///
+/// struct SmallInts;
+///
+/// impl<I: InlineId> Pack<u64, I> for SmallInts {
+///     type Unpacked<'a> = &'a u64 where I: 'a;
+///
+///     fn pack(value: &u64) -> Option<I> {
+///         if *value < 256 { I::pack_bytes(&[*value as u8]) } else { None }
+///     }
+///
+///     fn unpack(id: &I) -> Option<&u64> {
+///         match id.packed_bytes() {
+///             Some(&[n]) => Some(&SMALL[n as usize]),
+///             _ => None,
+///         }
+///     }
+/// }
+///
+/// symtern::adaptors::testkit::check_pack::<SmallInts, u64, u32>(&[&0, &7, &255, &256]);
+///
+/// let mut pool = Inline::<Pool<u64, u32>, SmallInts>::new();
+/// let seven = pool.intern(&7).expect("failed to intern a value");
+/// let large = pool.intern(&1_000_000).expect("failed to intern a value");
+/// assert_eq!((Ok(&7), Ok(&1_000_000)), (pool.resolve(&seven), pool.resolve(&large)));
+/// assert_eq!(1, pool.len());
+/// ```
+///
+/// [`Inline`]: struct.Inline.html
+/// [`Bytes`]: struct.Bytes.html
+/// [`Inlinable`]: trait.Inlinable.html
+/// [`testkit::check_pack`]: testkit/fn.check_pack.html
+/// [`DebugPack`]: trait.DebugPack.html
+/// [`InlineId::is_inlined`]: trait.InlineId.html#method.is_inlined
+pub trait Pack<T: ?Sized, I> {
+    /// Type of the values returned by `unpack`.  Values resolved by the
+    /// wrapped pool are converted to this type, so that `Inline` resolves
+    /// every symbol to the same type.
+    type Unpacked<'a>: From<&'a T> + Deref<Target=T> where T: 'a, I: 'a;
+
+    /// Pack a value into an ID, or return `None` if the value cannot be
+    /// inlined.
+    fn pack(value: &T) -> Option<I>;
+
+    /// Fetch the value packed into an ID, or `None` if the ID does not hold a
+    /// value packed by this encoder.
+    fn unpack(id: &I) -> Option<Self::Unpacked<'_>>;
+}
+
+/// Encoders that can format the values they pack, which lets [`InlineSym`]'s
+/// `Debug` implementation show an inlined symbol's value without consulting
+/// any pool.
+///
+/// Implemented by [`Bytes`] and [`Alphabet6`]; symbols created by an
+/// `Inline` adaptor implement `Debug` only if its encoder implements this
+/// trait.
+///
+/// [`InlineSym`]: struct.InlineSym.html
+/// [`Bytes`]: struct.Bytes.html
+/// [`Alphabet6`]: struct.Alphabet6.html
+pub trait DebugPack<I> {
+    /// Call `f` with a `Debug` view of the value packed into an ID, and return
+    /// its result, or return `None` if the ID does not hold a value packed by
+    /// this encoder.
+    fn with_debug_value(id: &I, f: &mut dyn FnMut(&dyn fmt::Debug) -> fmt::Result) -> Option<fmt::Result>;
+}
+
+/// Default encoder for the [`Inline`] adaptor, which stores the bytes of
+/// [`Inlinable`] values shorter than the ID type.
+///
+/// [`Inline`]: struct.Inline.html
+/// [`Inlinable`]: trait.Inlinable.html
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Bytes;
+
+impl<T, I> Pack<T, I> for Bytes
+    where T: ?Sized + Inlinable,
+          I: InlineId
+{
+    type Unpacked<'a> = &'a T where T: 'a, I: 'a;
+
+    fn pack(value: &T) -> Option<I> {
+        value.to_inline_bytes().and_then(I::pack_bytes)
+    }
+
+    fn unpack(id: &I) -> Option<&T> {
+        id.packed_bytes().and_then(T::from_inline_bytes)
+    }
+}
+
+/// Inlined bytes are formatted as a string if they are valid UTF-8, and as a
+/// byte slice otherwise.
+impl<I: InlineId> DebugPack<I> for Bytes {
+    fn with_debug_value(id: &I, f: &mut dyn FnMut(&dyn fmt::Debug) -> fmt::Result) -> Option<fmt::Result> {
+        id.packed_bytes().map(|bytes| match str::from_utf8(bytes) {
+            Ok(s) => f(&s),
+            Err(_) => f(&bytes),
+        })
+    }
+}
+
+
+/// Encoder for the [`Inline`] adaptor that packs identifier-like strings
+/// into six bits per character.
+///
+/// Strings made up of the 63 characters `[0-9A-Za-z_]` are inlined if they
+/// fit; all others are passed to the wrapped pool.  That allows longer
+/// strings than [`Bytes`] for a given ID type:
+///
+/// | ID type        | `Bytes` | `Alphabet6` |
+/// |----------------|---------|-------------|
+/// | `u32`          | 3       | 5           |
+/// | `u64`          | 7       | 10          |
+/// | `u128`         | 15      | 21          |
+///
+/// ```rust
+/// use symtern::prelude::*;
+/// use symtern::Pool;
+/// use symtern::adaptors::{Inline, Alphabet6};
+///
+/// let mut pool = Inline::<Pool<str, u64>, Alphabet6>::new();
+/// let ident = pool.intern("into_iter").expect("failed to intern a value");
+/// let other = pool.intern("a-b").expect("failed to intern a value");
+/// assert_eq!((Ok("into_iter"), Ok("a-b")),
+///            (pool.resolve(&ident).as_deref(), pool.resolve(&other).as_deref()));
+/// assert_eq!(1, pool.len());
+/// ```
+///
+/// Since the text cannot be borrowed from a symbol's ID, symbols resolve to
+/// `Cow<str>`: inlined symbols' text is decoded into a new `String`, and
+/// other symbols' text is borrowed from the wrapped pool.
+///
+/// IDs packed by `Alphabet6` have their second-most-significant bit set,
+/// which `Bytes` never sets, so symbols inlined by one encoder are never
+/// mistaken for symbols inlined by the other.
+///
+/// [`Inline`]: struct.Inline.html
+/// [`Bytes`]: struct.Bytes.html
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Alphabet6;
+
+/// Characters encoded by `Alphabet6`, in code order; code zero marks the end
+/// of a string.
+const ALPHABET6: &[u8; 63] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ_abcdefghijklmnopqrstuvwxyz";
+
+impl Alphabet6 {
+    /// Fetch the six-bit code for a character, if it is in the alphabet.
+    fn code(c: u8) -> Option<u128> {
+        match c {
+            b'0'..=b'9' => Some(u128::from(c - b'0') + 1),
+            b'A'..=b'Z' => Some(u128::from(c - b'A') + 11),
+            b'_' => Some(37),
+            b'a'..=b'z' => Some(u128::from(c - b'a') + 38),
+            _ => None,
+        }
+    }
+
+    /// Fetch the character codes stored in an ID, if it holds a string
+    /// packed by `Alphabet6`.
+    fn packed_codes<I: InlineId>(id: &I) -> Option<u128> {
+        if ! id.is_inlined() { return None; }
+        let marker = 1 << (I::PAYLOAD_BITS - 1);
+        let payload = id.payload();
+        if payload & marker == 0 { return None; }
+        let codes = payload & ! marker;
+
+        // Only trailing codes may be zero.
+        let len = (0..(I::PAYLOAD_BITS - 1) / 6).take_while(|i| (codes >> (i * 6)) & 0x3f != 0).count() as u32;
+        if codes >> (len * 6) != 0 { return None; }
+        Some(codes)
+    }
+
+    /// Decode a string's character codes.
+    fn decode_codes(codes: u128) -> String {
+        (0..21).map(|i| ((codes >> (i * 6)) & 0x3f) as usize)
+            .take_while(|&code| code != 0)
+            .map(|code| char::from(ALPHABET6[code - 1]))
+            .collect()
+    }
+}
+
+impl<I: InlineId> Pack<str, I> for Alphabet6 {
+    type Unpacked<'a> = Cow<'a, str> where I: 'a;
+
+    fn pack(value: &str) -> Option<I> {
+        if value.len() > ((I::PAYLOAD_BITS - 1) / 6) as usize { return None; }
+        let mut payload = 1 << (I::PAYLOAD_BITS - 1);
+        for (i, &c) in value.as_bytes().iter().enumerate() {
+            payload |= Self::code(c)? << (i * 6);
+        }
+        I::from_payload(payload)
+    }
+
+    fn unpack(id: &I) -> Option<Cow<'_, str>> {
+        Self::packed_codes(id).map(|codes| Cow::Owned(Self::decode_codes(codes)))
+    }
+}
+
+impl<I: InlineId> DebugPack<I> for Alphabet6 {
+    fn with_debug_value(id: &I, f: &mut dyn FnMut(&dyn fmt::Debug) -> fmt::Result) -> Option<fmt::Result> {
+        Self::packed_codes(id).map(|codes| f(&Self::decode_codes(codes)))
+    }
+}
+
+
+/// Interface for values that the [`Bytes`](struct.Bytes.html) encoder can
+/// store directly inside symbols, as a short sequence of bytes.
+///
+/// Implemented for `str`, `[u8]`, `OsStr` and `Path`.
+pub trait Inlinable {
+    /// Fetch the bytes that represent the value, or `None` if the value has
+    /// no byte representation that can be inlined.
+    fn to_inline_bytes(&self) -> Option<&[u8]>;
+
+    /// Reconstruct a value from bytes returned by `to_inline_bytes`.
+    /// Returns `None` if the bytes do not represent a valid value, which can
+    /// happen when an adaptor is given a symbol created by an adaptor over a
+    /// different input type.
+    fn from_inline_bytes(bytes: &[u8]) -> Option<&Self>;
+}
+
+impl Inlinable for str {
+    fn to_inline_bytes(&self) -> Option<&[u8]> {
+        Some(self.as_bytes())
+    }
+
+    fn from_inline_bytes(bytes: &[u8]) -> Option<&Self> {
+        str::from_utf8(bytes).ok()
+    }
+}
+
+impl Inlinable for [u8] {
+    fn to_inline_bytes(&self) -> Option<&[u8]> {
+        Some(self)
+    }
+
+    fn from_inline_bytes(bytes: &[u8]) -> Option<&Self> {
+        Some(bytes)
+    }
+}
+
+/// On Unix, any byte sequence is a valid `OsStr`; elsewhere, only
+/// valid-UTF-8 values are inlined.
+impl Inlinable for OsStr {
+    #[cfg(unix)]
+    fn to_inline_bytes(&self) -> Option<&[u8]> {
+        Some(OsStrExt::as_bytes(self))
+    }
+
+    #[cfg(unix)]
+    fn from_inline_bytes(bytes: &[u8]) -> Option<&Self> {
+        Some(OsStrExt::from_bytes(bytes))
+    }
+
+    #[cfg(not(unix))]
+    fn to_inline_bytes(&self) -> Option<&[u8]> {
+        self.to_str().map(str::as_bytes)
+    }
+
+    #[cfg(not(unix))]
+    fn from_inline_bytes(bytes: &[u8]) -> Option<&Self> {
+        str::from_utf8(bytes).ok().map(OsStr::new)
+    }
+}
+
+impl Inlinable for Path {
+    fn to_inline_bytes(&self) -> Option<&[u8]> {
+        self.as_os_str().to_inline_bytes()
+    }
+
+    fn from_inline_bytes(bytes: &[u8]) -> Option<&Self> {
+        OsStr::from_inline_bytes(bytes).map(Path::new)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+    use super::{Alphabet6, Bytes, InlineId, Pack};
+    use crate::adaptors::testkit::check_pack;
+
+    /// Check that `Alphabet6` fits the documented number of characters in
+    /// each ID type, and rejects strings outside its alphabet.
+    #[test]
+    fn alphabet6_capacity() {
+        fn fits<I: InlineId>(len: usize) -> bool {
+            <Alphabet6 as Pack<str, I>>::pack(&"abcdefghijklmnopqrstuvwxyz"[..len]).is_some()
+        }
+        assert!(fits::<u16>(2) && ! fits::<u16>(3));
+        assert!(fits::<u32>(5) && ! fits::<u32>(6));
+        assert!(fits::<u64>(10) && ! fits::<u64>(11));
+        assert!(fits::<u128>(21) && ! fits::<u128>(22));
+        assert_eq!(None, <Alphabet6 as Pack<str, u64>>::pack("a-b"));
+        assert_eq!(None, <Alphabet6 as Pack<str, u64>>::pack("ä"));
+
+        check_pack::<Alphabet6, str, u64>(&["", "x", "_0", "Zz9_", "into_iter", "get_value1", "get_values_"]);
+        check_pack::<Alphabet6, str, NonZeroU32>(&["", "ab", "Abc_9", "abcdef"]);
+        check_pack::<Alphabet6, str, u128>(&["0123456789ABCDEFGHIJK", "0123456789ABCDEFGHIJKL"]);
+    }
+
+    /// Check that each encoder ignores IDs packed by the other.
+    #[test]
+    fn encoders_do_not_confuse_ids() {
+        let bytes: u64 = Bytes::pack("abc").expect("failed to pack string");
+        let alpha: u64 = Alphabet6::pack("abc").expect("failed to pack string");
+        assert_eq!(None, <Alphabet6 as Pack<str, u64>>::unpack(&bytes));
+        assert_eq!(None, <Bytes as Pack<str, u64>>::unpack(&alpha));
+        assert_eq!(Some("abc"), <Alphabet6 as Pack<str, u64>>::unpack(&alpha).as_deref());
+    }
+}
@@ -0,0 +1,596 @@
+// Copyright (C) 2016-2017 Symtern Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-Apache
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or
+// distributed except according to those terms.
+//! Reference-counting interner adaptors.
+// [Module documentation lives on the exported adaptors, `Refcounted` and
+// `SyncRefcounted`.]
+use std::borrow::ToOwned;
+use std::cell::{RefCell, Ref, RefMut};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::{sym, traits, ErrorKind, Result};
+use crate::core::HashMap;
+
+/// Wrapped pool, and the number of live handles for each of its symbols.
+struct Counts<W: sym::Pool> {
+    pool: W,
+    counts: HashMap<W::Symbol, usize>,
+    /// Function used to remove a value from the wrapped pool.  Storing it here
+    /// lets handles be dropped without naming the pool's `Remove` bound.
+    remove: fn(&mut W, W::Symbol),
+}
+
+/// Remove a value whose last handle has been dropped from a wrapped pool.
+fn remove<W>(pool: &mut W, symbol: W::Symbol)
+    where W: sym::Pool,
+          for<'b> &'b mut W: traits::Remove<Symbol=W::Symbol>
+{
+    // The symbol was created by the wrapped pool and, until now, has had
+    // a live handle, so removal cannot fail.
+    let _ = traits::Remove::remove(pool, symbol);
+}
+
+impl<W: sym::Pool> Counts<W> {
+    fn new(pool: W) -> Self
+        where for<'b> &'b mut W: traits::Remove<Symbol=W::Symbol>
+    {
+        Counts{pool, counts: HashMap::default(), remove: remove::<W>}
+    }
+
+    /// Intern a value in the wrapped pool, and count a new handle for its
+    /// symbol.
+    fn acquire<BI: ?Sized>(&mut self, value: &BI) -> Result<W::Symbol>
+        where for<'b> &'b mut W: traits::Intern<Input=BI, Symbol=W::Symbol>
+    {
+        let symbol = traits::Intern::intern(&mut self.pool, value)?;
+        *self.counts.entry(symbol).or_insert(0) += 1;
+        Ok(symbol)
+    }
+
+    /// Count a new handle for the symbol of a value already in the wrapped
+    /// pool.
+    fn acquire_existing<BI: ?Sized>(&mut self, value: &BI) -> Option<W::Symbol>
+        where for<'b> &'b W: traits::Lookup<Input=BI, Symbol=W::Symbol>
+    {
+        let symbol = traits::Lookup::get(&self.pool, value)?;
+        *self.counts.entry(symbol).or_insert(0) += 1;
+        Some(symbol)
+    }
+
+    /// Count a new handle for a symbol that already has at least one.
+    fn retain(&mut self, symbol: W::Symbol) {
+        *self.counts.get_mut(&symbol).expect("Unexpected missing count for live symbol") += 1;
+    }
+
+    /// Forget a handle for the given symbol, and remove its value from the
+    /// wrapped pool if no handles remain.
+    fn release(&mut self, symbol: W::Symbol) {
+        let count = self.counts.get_mut(&symbol).expect("Unexpected missing count for live symbol");
+        *count -= 1;
+        if *count == 0 {
+            self.counts.remove(&symbol);
+            (self.remove)(&mut self.pool, symbol);
+        }
+    }
+}
+
+// ----------------------------------------------------------------
+
+/// State shared between a `Refcounted` adaptor and its handles.
+///
+/// The counts live in a separate cell from the wrapped pool, so that handles
+/// can be cloned and dropped while a resolved value is borrowing the pool.
+struct Shared<W: sym::Pool> {
+    pool: RefCell<W>,
+    counts: RefCell<HashMap<W::Symbol, usize>>,
+    /// Symbols whose last handle was dropped while the pool was borrowed by
+    /// a resolved value; their values are removed before the pool is next
+    /// borrowed mutably.
+    deferred: RefCell<Vec<W::Symbol>>,
+    /// Function used to remove a value from the wrapped pool.
+    remove: fn(&mut W, W::Symbol),
+}
+
+impl<W: sym::Pool> Shared<W> {
+    /// Remove the values of any symbols whose release was deferred.
+    fn flush(&self, pool: &mut W) {
+        for symbol in self.deferred.borrow_mut().drain(..) {
+            (self.remove)(pool, symbol);
+        }
+    }
+
+    /// Borrow the wrapped pool mutably, first removing the values of any
+    /// deferred symbols, or fail if a resolved value is borrowing it.
+    fn pool_mut(&self) -> Result<RefMut<'_, W>> {
+        let mut pool = self.pool.try_borrow_mut().map_err(|_| ErrorKind::PoolBorrowed)?;
+        self.flush(&mut pool);
+        Ok(pool)
+    }
+
+    /// Count a new handle for the given symbol.
+    fn retain(&self, symbol: W::Symbol) {
+        let mut counts = self.counts.borrow_mut();
+        let count = counts.entry(symbol).or_insert(0);
+        if *count == 0 {
+            // The symbol's last handle may have been dropped while the pool
+            // was borrowed; its value is in use again, so keep it.
+            self.deferred.borrow_mut().retain(|&s| s != symbol);
+        }
+        *count += 1;
+    }
+
+    /// Forget a handle for the given symbol, and remove its value from the
+    /// wrapped pool if no handles remain.
+    fn release(&self, symbol: W::Symbol) {
+        {
+            let mut counts = self.counts.borrow_mut();
+            let count = counts.get_mut(&symbol).expect("Unexpected missing count for live symbol");
+            *count -= 1;
+            if *count > 0 {
+                return;
+            }
+            counts.remove(&symbol);
+        }
+        match self.pool.try_borrow_mut() {
+            Ok(mut pool) => (self.remove)(&mut pool, symbol),
+            // A resolved value is still borrowing the pool.
+            Err(_) => self.deferred.borrow_mut().push(symbol),
+        }
+    }
+}
+
+/// Single-threaded interner adaptor whose symbols are reference-counted
+/// handles.
+///
+/// Each [`RcSym`] returned by this adaptor keeps its value alive in the
+/// wrapped pool: cloning a handle increments a count for its symbol, and once
+/// the last handle for a symbol is dropped its value is removed.  Interning
+/// a value again while a handle for it is alive returns a handle for the same
+/// symbol.
+///
+/// The wrapped pool must support [`Remove`], so it will usually be
+/// a [`RecyclingPool`] &mdash; which reuses the IDs of removed values, keeping
+/// memory use proportional to the number of values in use at any one time.
+///
+/// ```rust
+/// use symtern::prelude::*;
+/// use symtern::RecyclingPool;
+/// use symtern::adaptors::Refcounted;
+///
+/// let pool = Refcounted::<RecyclingPool<str, u32>>::new();
+/// let a = pool.intern("identifier").expect("failed to intern a value");
+/// let b = pool.intern("identifier").expect("failed to intern a value");
+/// assert_eq!(a, b);
+/// assert_eq!("identifier", &*pool.resolve(&a).expect("failed to resolve a value"));
+///
+/// drop(a);
+/// assert_eq!(1, pool.len());
+/// drop(b);
+/// assert!(pool.is_empty());
+/// ```
+///
+/// Because handles are not `Copy`, this adaptor's `intern` and `get` are
+/// inherent methods rather than implementations of the [`Intern`] and
+/// [`Lookup`] traits.  Handles hold a reference to the adaptor's state, so
+/// dropping the adaptor itself does not invalidate them.
+///
+/// A value returned by `resolve` borrows the wrapped pool.  While it is
+/// alive, handles can still be cloned, dropped and looked up, but `intern`
+/// fails with [`ErrorKind::PoolBorrowed`].
+///
+/// [`RcSym`]: struct.RcSym.html
+/// [`Remove`]: ../traits/trait.Remove.html
+/// [`Intern`]: ../traits/trait.Intern.html
+/// [`Lookup`]: ../traits/trait.Lookup.html
+/// [`RecyclingPool`]: ../struct.RecyclingPool.html
+/// [`ErrorKind::PoolBorrowed`]: ../enum.ErrorKind.html#variant.PoolBorrowed
+pub struct Refcounted<W: sym::Pool> {
+    shared: Rc<Shared<W>>,
+}
+
+/// Reference-counted symbol type used by the
+/// [`Refcounted`](struct.Refcounted.html) adaptor.
+pub struct RcSym<W>
+    where W: sym::Pool
+{
+    symbol: W::Symbol,
+    shared: Rc<Shared<W>>,
+}
+
+impl<W> Refcounted<W>
+    where W: sym::Pool
+{
+    /// Create a new, empty `Refcounted` instance.
+    pub fn new() -> Self
+        where W: Default,
+              for<'b> &'b mut W: traits::Remove<Symbol=W::Symbol>
+    {
+        Refcounted{shared: Rc::new(Shared{pool: RefCell::new(W::default()),
+                                          counts: RefCell::new(HashMap::default()),
+                                          deferred: RefCell::new(Vec::new()),
+                                          remove: remove::<W>})}
+    }
+
+    /// Intern a value, and return a handle that keeps it in the pool.
+    ///
+    /// Fails with `ErrorKind::PoolBorrowed` if a value returned by `resolve`
+    /// is still alive.
+    pub fn intern<BI: ?Sized>(&self, value: &BI) -> Result<RcSym<W>>
+        where for<'b> &'b mut W: traits::Intern<Input=BI, Symbol=W::Symbol>
+    {
+        let symbol = traits::Intern::intern(&mut *self.shared.pool_mut()?, value)?;
+        self.shared.retain(symbol);
+        Ok(RcSym{symbol, shared: Rc::clone(&self.shared)})
+    }
+
+    /// Fetch a handle for the given value, if it is in the pool.
+    pub fn get<BI: ?Sized>(&self, value: &BI) -> Option<RcSym<W>>
+        where for<'b> &'b W: traits::Lookup<Input=BI, Symbol=W::Symbol>
+    {
+        if let Ok(mut pool) = self.shared.pool.try_borrow_mut() {
+            self.shared.flush(&mut pool);
+        }
+        let symbol = traits::Lookup::get(&*self.shared.pool.borrow(), value)?;
+        self.shared.retain(symbol);
+        Some(RcSym{symbol, shared: Rc::clone(&self.shared)})
+    }
+}
+
+impl<W> Default for Refcounted<W>
+    where W: sym::Pool + Default,
+          for<'b> &'b mut W: traits::Remove<Symbol=W::Symbol>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, W, BO: ?Sized> traits::Resolve for &'a Refcounted<W>
+    where W: sym::Pool,
+          for<'b> &'b W: traits::Resolve<Input=W::Symbol, Output=&'b BO>,
+          BO: 'a
+{
+    type Input = &'a RcSym<W>;
+    type Output = Ref<'a, BO>;
+
+    fn resolve(self, handle: Self::Input) -> Result<Self::Output> {
+        debug_assert!(Rc::ptr_eq(&self.shared, &handle.shared),
+                      "Detected an invalid attempt to resolve a symbol on a pool that did not create it");
+        let pool = self.shared.pool.borrow();
+        traits::Resolve::resolve(&*pool, handle.symbol)?;
+        Ok(Ref::map(pool, |p| traits::Resolve::resolve(p, handle.symbol).unwrap()))
+    }
+}
+
+impl<W> traits::Len for Refcounted<W>
+    where W: sym::Pool + traits::Len
+{
+    /// Get the number of values that have live handles.
+    fn len(&self) -> usize {
+        // Values whose removal was deferred no longer have handles.
+        self.shared.pool.borrow().len() - self.shared.deferred.borrow().len()
+    }
+    fn is_full(&self) -> bool {
+        self.shared.pool.borrow().is_full()
+    }
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<W> RcSym<W>
+    where W: sym::Pool
+{
+    /// Fetch the wrapped pool's symbol for the handle's value.  The returned
+    /// symbol does not keep the value alive.
+    pub fn symbol(&self) -> W::Symbol {
+        self.symbol
+    }
+}
+
+impl<W> Clone for RcSym<W>
+    where W: sym::Pool
+{
+    fn clone(&self) -> Self {
+        self.shared.retain(self.symbol);
+        RcSym{symbol: self.symbol, shared: Rc::clone(&self.shared)}
+    }
+}
+
+impl<W> Drop for RcSym<W>
+    where W: sym::Pool
+{
+    fn drop(&mut self) {
+        self.shared.release(self.symbol)
+    }
+}
+
+impl<W> PartialEq for RcSym<W>
+    where W: sym::Pool
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.symbol == other.symbol && Rc::ptr_eq(&self.shared, &other.shared)
+    }
+}
+
+impl<W> Eq for RcSym<W>
+    where W: sym::Pool
+{}
+
+impl<W> Hash for RcSym<W>
+    where W: sym::Pool
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.symbol.hash(state)
+    }
+}
+
+impl<W> fmt::Debug for RcSym<W>
+    where W: sym::Pool,
+          W::Symbol: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("RcSym").field(&self.symbol).finish()
+    }
+}
+
+// ----------------------------------------------------------------
+
+/// Thread-safe interner adaptor whose symbols are reference-counted handles.
+///
+/// This is the thread-safe counterpart to [`Refcounted`]: the wrapped pool is
+/// protected by a `Mutex`, and its symbols are [`ArcSym`] handles that can be
+/// sent between threads.  Since resolved values cannot borrow from the pool
+/// once its lock is released, resolving a symbol returns an owned copy of
+/// its value.
+///
+/// ```rust
+/// use std::thread;
+/// use symtern::prelude::*;
+/// use symtern::RecyclingPool;
+/// use symtern::adaptors::SyncRefcounted;
+///
+/// let pool = SyncRefcounted::<RecyclingPool<str, u32>>::new();
+/// let sym = pool.intern("shared").expect("failed to intern a value");
+/// let remote = sym.clone();
+/// thread::spawn(move || drop(remote)).join().unwrap();
+///
+/// assert_eq!(Ok(String::from("shared")), pool.resolve(&sym));
+/// drop(sym);
+/// assert!(pool.is_empty());
+/// ```
+///
+/// [`Refcounted`]: struct.Refcounted.html
+/// [`ArcSym`]: struct.ArcSym.html
+pub struct SyncRefcounted<W: sym::Pool> {
+    shared: Arc<Mutex<Counts<W>>>,
+}
+
+/// Reference-counted symbol type used by the
+/// [`SyncRefcounted`](struct.SyncRefcounted.html) adaptor.
+pub struct ArcSym<W>
+    where W: sym::Pool
+{
+    symbol: W::Symbol,
+    shared: Arc<Mutex<Counts<W>>>,
+}
+
+/// Lock a `SyncRefcounted` adaptor's state.  Counts are never left in an
+/// inconsistent state, so we ignore lock poisoning.
+fn lock<W: sym::Pool>(shared: &Mutex<Counts<W>>) -> MutexGuard<'_, Counts<W>> {
+    shared.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl<W> SyncRefcounted<W>
+    where W: sym::Pool
+{
+    /// Create a new, empty `SyncRefcounted` instance.
+    pub fn new() -> Self
+        where W: Default,
+              for<'b> &'b mut W: traits::Remove<Symbol=W::Symbol>
+    {
+        SyncRefcounted{shared: Arc::new(Mutex::new(Counts::<W>::new(W::default())))}
+    }
+
+    /// Intern a value, and return a handle that keeps it in the pool.
+    pub fn intern<BI: ?Sized>(&self, value: &BI) -> Result<ArcSym<W>>
+        where for<'b> &'b mut W: traits::Intern<Input=BI, Symbol=W::Symbol>
+    {
+        let symbol = lock(&self.shared).acquire(value)?;
+        Ok(ArcSym{symbol, shared: Arc::clone(&self.shared)})
+    }
+
+    /// Fetch a handle for the given value, if it is in the pool.
+    pub fn get<BI: ?Sized>(&self, value: &BI) -> Option<ArcSym<W>>
+        where for<'b> &'b W: traits::Lookup<Input=BI, Symbol=W::Symbol>
+    {
+        let symbol = lock(&self.shared).acquire_existing(value)?;
+        Some(ArcSym{symbol, shared: Arc::clone(&self.shared)})
+    }
+}
+
+impl<W> Default for SyncRefcounted<W>
+    where W: sym::Pool + Default,
+          for<'b> &'b mut W: traits::Remove<Symbol=W::Symbol>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, W, BO: ?Sized> traits::Resolve for &'a SyncRefcounted<W>
+    where W: sym::Pool,
+          for<'b> &'b W: traits::Resolve<Input=W::Symbol, Output=&'b BO>,
+          BO: ToOwned
+{
+    type Input = &'a ArcSym<W>;
+    type Output = BO::Owned;
+
+    fn resolve(self, handle: Self::Input) -> Result<Self::Output> {
+        debug_assert!(Arc::ptr_eq(&self.shared, &handle.shared),
+                      "Detected an invalid attempt to resolve a symbol on a pool that did not create it");
+        traits::Resolve::resolve(&lock(&self.shared).pool, handle.symbol).map(ToOwned::to_owned)
+    }
+}
+
+impl<W> traits::Len for SyncRefcounted<W>
+    where W: sym::Pool + traits::Len
+{
+    /// Get the number of values that have live handles.
+    fn len(&self) -> usize {
+        lock(&self.shared).pool.len()
+    }
+    fn is_full(&self) -> bool {
+        lock(&self.shared).pool.is_full()
+    }
+    fn is_empty(&self) -> bool {
+        lock(&self.shared).pool.is_empty()
+    }
+}
+
+impl<W> ArcSym<W>
+    where W: sym::Pool
+{
+    /// Fetch the wrapped pool's symbol for the handle's value.  The returned
+    /// symbol does not keep the value alive.
+    pub fn symbol(&self) -> W::Symbol {
+        self.symbol
+    }
+}
+
+impl<W> Clone for ArcSym<W>
+    where W: sym::Pool
+{
+    fn clone(&self) -> Self {
+        lock(&self.shared).retain(self.symbol);
+        ArcSym{symbol: self.symbol, shared: Arc::clone(&self.shared)}
+    }
+}
+
+impl<W> Drop for ArcSym<W>
+    where W: sym::Pool
+{
+    fn drop(&mut self) {
+        lock(&self.shared).release(self.symbol)
+    }
+}
+
+impl<W> PartialEq for ArcSym<W>
+    where W: sym::Pool
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.symbol == other.symbol && Arc::ptr_eq(&self.shared, &other.shared)
+    }
+}
+
+impl<W> Eq for ArcSym<W>
+    where W: sym::Pool
+{}
+
+impl<W> Hash for ArcSym<W>
+    where W: sym::Pool
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.symbol.hash(state)
+    }
+}
+
+impl<W> fmt::Debug for ArcSym<W>
+    where W: sym::Pool,
+          W::Symbol: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("ArcSym").field(&self.symbol).finish()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::ErrorKind;
+    use crate::recycling::Pool;
+    use super::{Refcounted, SyncRefcounted};
+
+    /// Check that a value stays in the pool until its last handle is dropped.
+    #[test]
+    fn frees_value_when_last_handle_drops() {
+        let pool = Refcounted::<Pool<str, u16>>::new();
+        let a = pool.intern("a").expect("failed to intern value");
+        let a2 = pool.intern("a").expect("failed to intern value");
+        let a3 = pool.get("a").expect("failed to look up value");
+        let a4 = a.clone();
+        assert_eq!(a.symbol(), a2.symbol());
+        assert_eq!(a, a3);
+
+        drop(a);
+        drop(a2);
+        drop(a3);
+        assert_eq!(1, pool.len());
+        assert_eq!("a", &*pool.resolve(&a4).expect("failed to resolve value"));
+        drop(a4);
+        assert!(pool.is_empty());
+        assert!(pool.get("a").is_none());
+    }
+
+    /// Check that handles dropped while a resolved value is borrowed are
+    /// released later.
+    #[test]
+    fn defers_release_while_resolved_value_is_borrowed() {
+        let pool = Refcounted::<Pool<str, u16>>::new();
+        let a = pool.intern("a").expect("failed to intern value");
+        let b = pool.intern("b").expect("failed to intern value");
+        {
+            let value = pool.resolve(&a).expect("failed to resolve value");
+            drop(b);
+            assert_eq!("a", &*value);
+        }
+        assert_eq!(1, pool.len());
+        assert!(pool.get("b").is_none());
+    }
+
+    /// Check that handles can be cloned and counted, and values looked up,
+    /// while a resolved value is borrowed, and that interning fails instead
+    /// of panicking.
+    #[test]
+    fn handles_work_while_resolved_value_is_borrowed() {
+        let pool = Refcounted::<Pool<str, u16>>::new();
+        let a = pool.intern("a").expect("failed to intern value");
+        let b = pool.intern("b").expect("failed to intern value");
+        {
+            let value = pool.resolve(&a).expect("failed to resolve value");
+            let a2 = a.clone();
+            assert_eq!(2, pool.len());
+            assert_eq!(ErrorKind::PoolBorrowed, pool.intern("c").unwrap_err().kind());
+
+            drop(b);
+            assert_eq!(1, pool.len());
+            // Looking up a value whose removal was deferred keeps it.
+            let b = pool.get("b").expect("failed to look up value");
+            assert_eq!(2, pool.len());
+            drop(b);
+            drop(a2);
+            assert_eq!("a", &*value);
+        }
+        assert_eq!(1, pool.len());
+        assert!(pool.get("b").is_none());
+        assert_eq!("a", &*pool.resolve(&a).expect("failed to resolve value"));
+    }
+
+    /// Check that handles can be dropped on other threads.
+    #[test]
+    fn sync_handles_are_released_across_threads() {
+        let pool = SyncRefcounted::<Pool<str, u32>>::new();
+        let handles: Vec<_> = (0..100).map(|i| pool.intern(&i.to_string()).expect("failed to intern value")).collect();
+        assert_eq!(100, pool.len());
+        ::std::thread::spawn(move || drop(handles)).join().unwrap();
+        assert!(pool.is_empty());
+    }
+}
@@ -0,0 +1,60 @@
+// Copyright (C) 2016-2017 Symtern Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-Apache
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or
+// distributed except according to those terms.
+//! Checks for custom [`Pack`](../trait.Pack.html) encoders, for use in their
+//! implementors' tests.
+use std::fmt::Debug;
+
+use super::{InlineId, Pack};
+
+/// Check that encoder `E` upholds the rules of the [`Pack`] trait for the
+/// given sample values, panicking with a description of the first violation
+/// found.
+///
+/// For each value that `E` packs, this checks that
+///
+///   * the packed ID has its most-significant bit set, so that it cannot
+///     collide with any ID assigned by a wrapped pool; and
+///   * unpacking the ID yields a value equal to the original, which also
+///     ensures that unequal values are never packed into the same ID.
+///
+/// Values that `E` declines to pack are skipped, so samples should include
+/// values on both sides of any size limit.
+///
+/// [`Pack`]: ../trait.Pack.html
+pub fn check_pack<E, T, I>(values: &[&T])
+    where E: Pack<T, I>,
+          T: ?Sized + PartialEq + Debug,
+          I: InlineId + Debug
+{
+    for &value in values {
+        if let Some(id) = E::pack(value) {
+            assert!(id.is_inlined(),
+                    "Value {:?} was packed into ID {:?}, which does not have its most-significant bit set", value, id);
+            assert_eq!(Some(value), E::unpack(&id).as_deref(),
+                       "Value {:?} did not round-trip through ID {:?}", value, id);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+    use super::check_pack;
+    use std::num::NonZeroU32;
+    use crate::adaptors::Bytes;
+
+    /// Check that the default encoder passes its own checks.
+    #[test]
+    fn bytes_encoder_passes() {
+        check_pack::<Bytes, str, u64>(&["", "a", "abcdefg", "abcdefgh"]);
+        check_pack::<Bytes, str, NonZeroU32>(&["", "abc", "abcd"]);
+        check_pack::<Bytes, [u8], u128>(&[&[0xff][..], &[0; 15][..], &[0; 16][..]]);
+        check_pack::<Bytes, OsStr, u32>(&[OsStr::new("x"), OsStr::new("xyz")]);
+    }
+}
@@ -0,0 +1,443 @@
+// Copyright (C) 2016-2017 Symtern Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-Apache
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or
+// distributed except according to those terms.
+//! Append-only interner that can intern through a shared reference.
+// [Module documentation lives on the exported pool type, `AppendPool`.]
+use std::borrow::{Borrow, ToOwned};
+use std::cell::{Cell, RefCell};
+use std::hash::{BuildHasher, Hash};
+
+use crate::traits::{Intern, Lookup, Resolve, ResolveUnchecked, Iterate, Len, SymbolId};
+use crate::{core, Result, ErrorKind};
+use crate::core::{locate, Bucket, DefaultBuildHasher, HashMap, FIRST_CHUNK_LEN};
+use crate::sym::{Symbol as ISymbol, Pool as IPool};
+use crate::basic::Sym;
+
+/// Append-only interner that allows interning through a shared reference.
+///
+/// Values stored in an `AppendPool` are never moved or removed: they are kept
+/// in a list of fixed-capacity chunks, and a new chunk is allocated whenever
+/// the last one fills up.  This means that the references returned by
+/// [`resolve`] remain valid for as long as the pool itself, and `intern` can
+/// take `&self` &mdash; so, unlike with a [`Luma`] adaptor over other pools,
+/// interning and resolution can be freely interleaved without any risk of a
+/// run-time borrow error.
+///
+/// ```rust file="examples/append-only.rs"
+/// use symtern::prelude::*;
+/// use symtern::AppendPool;
+///
+/// let pool = AppendPool::<str, u32>::new();
+/// let x = pool.intern("foo").expect("failed to intern a value");
+/// let foo = pool.resolve(x).expect("failed to resolve the value we just interned");
+///
+/// // We can keep using `foo` while interning new values.
+/// let y = pool.intern("bar").expect("failed to intern a value");
+/// assert_eq!("foo", foo);
+/// assert_eq!(Ok("bar"), pool.resolve(y));
+/// ```
+///
+/// `AppendPool` uses `RefCell` internally, so it cannot be shared between
+/// threads.
+///
+/// [`resolve`]: traits/trait.Resolve.html#tymethod.resolve
+/// [`Luma`]: adaptors/struct.Luma.html
+#[derive(Debug)]
+pub struct Pool<T: ?Sized, I = usize, S = DefaultBuildHasher>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    ids_map: RefCell<HashMap<u64, Bucket<I>>>,
+    chunks: RefCell<Vec<Vec<T::Owned>>>,
+    len: Cell<usize>,
+    hash_builder: S,
+    #[cfg(debug_assertions)]
+    pool_id: usize
+}
+
+// (inherent impl)
+impl<T: ?Sized, I> Pool<T, I>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    /// Create a new, empty `AppendPool` instance.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<T: ?Sized, I, S> Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId,
+          S: BuildHasher
+{
+    /// Create a new, empty `AppendPool` instance that will use the given
+    /// hasher factory to hash interned values.
+    #[cfg(not(debug_assertions))]
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Pool{ids_map: Default::default(),
+             chunks: Default::default(),
+             len: Cell::new(0),
+             hash_builder}
+    }
+
+    /// Create a new, empty `AppendPool` instance that will use the given
+    /// hasher factory to hash interned values.
+    #[cfg(debug_assertions)]
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Pool{ids_map: Default::default(),
+             chunks: Default::default(),
+             len: Cell::new(0),
+             hash_builder,
+             pool_id: crate::sym::next_pool_id()}
+    }
+
+    /// Fetch a reference to the pool's hasher factory.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+}
+
+impl<T: ?Sized, I, S> Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId
+{
+    /// Fetch the value stored at the given index, if any.
+    fn get_value(&self, idx: usize) -> Option<&T> {
+        let (chunk, offset) = locate(idx);
+        let chunks = self.chunks.borrow();
+        chunks.get(chunk).and_then(|c| c.get(offset)).map(|value| {
+            let value: &T = value.borrow();
+            // Chunks are never grown beyond their initial capacity and values
+            // are never removed, so the stored value will not move or be
+            // dropped until the pool itself is dropped.
+            unsafe { &*(value as *const T) }
+        })
+    }
+
+    /// Find the ID of a stored value equal to `value`, given the value's hash.
+    fn find_id(&self, key: u64, value: &T) -> Option<I> {
+        let ids_map = self.ids_map.borrow();
+        ids_map.get(&key).and_then(|bucket| {
+            bucket.ids().iter().cloned().find(|id| {
+                let idx = id.to_usize().expect("Unexpected failure to convert symbol ID to usize");
+                self.get_value(idx) == Some(value)
+            })
+        })
+    }
+
+    /// Append a value to the pool's storage, returning its index.
+    fn push(&self, value: T::Owned) -> usize {
+        let idx = self.len.get();
+        let (chunk, _) = locate(idx);
+
+        let mut chunks = self.chunks.borrow_mut();
+        if chunk == chunks.len() {
+            chunks.push(Vec::with_capacity(FIRST_CHUNK_LEN << chunk));
+        }
+        chunks[chunk].push(value);
+        self.len.set(idx + 1);
+
+        idx
+    }
+}
+
+impl<T: ?Sized, I, S> Len for Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    /// Get the number of entries contained in the pool.
+    fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    /// Check if the pool is "empty", i.e. has zero stored values.
+    fn is_empty(&self) -> bool {
+        self.len.get() == 0
+    }
+
+    /// Check if the number of interned symbols has reached the maximum allowed
+    /// for the pool's ID type.
+    fn is_full(&self) -> bool {
+        // Symbol IDs range from 0 to M, where M is given by `I::max_value()`;
+        // hence a pool containing N entries is full iff N == M + 1.
+        self.len() > I::max_value().to_usize().unwrap_or(usize::MAX)
+    }
+}
+
+impl<T: ?Sized, I, S> crate::sym::Pool for Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    type Symbol = Sym<I>;
+
+    #[cfg(debug_assertions)]
+    fn id(&self) -> crate::sym::PoolId {
+        self.pool_id
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn create_symbol(&self, id: <Self::Symbol as crate::sym::Symbol>::Id) -> Self::Symbol {
+        Sym::create(id)
+    }
+
+    #[cfg(debug_assertions)]
+    fn create_symbol(&self, id: <Self::Symbol as crate::sym::Symbol>::Id) -> Self::Symbol {
+        Sym::create(id, self.id())
+    }
+}
+
+// Default
+impl<T: ?Sized, I, S> Default for Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId,
+          S: BuildHasher + Default
+{
+    fn default() -> Self {
+        Pool::with_hasher(Default::default())
+    }
+}
+
+// Intern
+impl<T: ?Sized, I, S> Intern for &Pool<T, I, S>
+    where I: SymbolId,
+          T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          S: BuildHasher,
+{
+    type Input = T;
+    type Symbol = Sym<I>;
+
+    fn intern(self, value: &Self::Input) -> Result<Self::Symbol> {
+        let key = core::hash(&self.hash_builder, value);
+        if let Some(id) = self.find_id(key, value) {
+            Ok(self.create_symbol(id))
+        } else if self.is_full() {
+            Err(ErrorKind::PoolOverflow.into())
+        } else {
+            let idx = self.push(value.to_owned());
+
+            // We do not expect this conversion to fail, since the condition in
+            // the previous branch (`is_full()`) checks if a new ID would be
+            // a representable value.
+            let id = I::from_usize(idx)
+                .expect("Unexpected failure to convert symbol ID from usize");
+            core::insert_id(&mut self.ids_map.borrow_mut(), key, id);
+
+            Ok(self.create_symbol(id))
+        }
+    }
+}
+
+// ----------------------------------------------------------------
+// Lookup
+impl<T: ?Sized, I, S> Lookup for &Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId,
+          S: BuildHasher,
+{
+    type Input = T;
+    type Symbol = Sym<I>;
+
+    fn get(self, value: &Self::Input) -> Option<Self::Symbol> {
+        let key = core::hash(&self.hash_builder, value);
+        self.find_id(key, value).map(|id| self.create_symbol(id))
+    }
+
+    fn contains_symbol(self, s: Self::Symbol) -> bool {
+        if is_foreign_symbol!(self, s) { return false; }
+        let idx = s.id().to_usize().expect("Unexpected failure to convert symbol ID to usize");
+        idx < self.len()
+    }
+}
+
+// ----------------------------------------------------------------
+// Resolve
+impl<'a, T: ?Sized, I, S> Resolve for &'a Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId
+{
+    type Input = Sym<I>;
+    type Output = &'a T;
+
+    fn resolve(self, s: Self::Input) -> Result<Self::Output> {
+        check_matching_pool!(self, s);
+        // We previously converted the ID _from_ a usize, so this conversion should _not_ fail.
+        let idx = s.id().to_usize().expect("Unexpected failure to convert symbol ID to usize");
+        self.get_value(idx).ok_or_else(|| ErrorKind::NoSuchSymbol.into())
+    }
+}
+
+impl<T: ?Sized, I, S> ResolveUnchecked for &Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId
+{
+    unsafe fn resolve_unchecked(self, symbol: Self::Input) -> Self::Output {
+        let idx = symbol.id().to_usize().expect("Unexpected failure to convert symbol ID to usize");
+        let (chunk, offset) = locate(idx);
+        let chunks = self.chunks.borrow();
+        let value: &T = chunks.get_unchecked(chunk).get_unchecked(offset).borrow();
+        &*(value as *const T)
+    }
+}
+
+// ----------------------------------------------------------------
+// Iteration
+
+/// Iterator over the symbols and values stored in an
+/// [`AppendPool`](struct.AppendPool.html), in order of increasing symbol ID.
+///
+/// The iterator visits only the values that were present when it was created.
+pub struct Iter<'a, T: ?Sized, I, S>
+    where T: 'a + ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: 'a + SymbolId,
+          S: 'a
+{
+    pool: &'a Pool<T, I, S>,
+    next: usize,
+    end: usize,
+}
+
+impl<'a, T: ?Sized, I, S> Iterator for Iter<'a, T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId
+{
+    type Item = (Sym<I>, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next == self.end {
+            return None;
+        }
+        let idx = self.next;
+        self.next += 1;
+
+        let id = I::from_usize(idx).expect("Unexpected failure to convert symbol ID from usize");
+        let value = self.pool.get_value(idx).expect("Unexpected missing value in append-only pool");
+        Some((self.pool.create_symbol(id), value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.end - self.next, Some(self.end - self.next))
+    }
+}
+
+impl<'a, T: ?Sized, I, S> ExactSizeIterator for Iter<'a, T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId
+{}
+
+impl<'a, T: ?Sized, I, S> Iterate for &'a Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId
+{
+    type Symbol = Sym<I>;
+    type Output = &'a T;
+    type Iter = Iter<'a, T, I, S>;
+
+    fn iter(self) -> Self::Iter {
+        Iter{pool: self, next: 0, end: self.len()}
+    }
+}
+
+impl<'a, T: ?Sized, I, S> IntoIterator for &'a Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId
+{
+    type Item = (Sym<I>, &'a T);
+    type IntoIter = Iter<'a, T, I, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Iterate::iter(self)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Pool;
+    use crate::core::{locate, FIRST_CHUNK_LEN};
+    use crate::traits::*;
+
+    #[test]
+    fn locates_values_in_chunks() {
+        assert_eq!((0, 0), locate(0));
+        assert_eq!((0, FIRST_CHUNK_LEN - 1), locate(FIRST_CHUNK_LEN - 1));
+        assert_eq!((1, 0), locate(FIRST_CHUNK_LEN));
+        assert_eq!((1, 2 * FIRST_CHUNK_LEN - 1), locate(3 * FIRST_CHUNK_LEN - 1));
+        assert_eq!((2, 0), locate(3 * FIRST_CHUNK_LEN));
+        assert_eq!((3, 0), locate(7 * FIRST_CHUNK_LEN));
+    }
+
+    /// Check that references returned by `resolve` remain valid while we
+    /// intern enough new values to allocate several more chunks.
+    #[test]
+    fn resolved_values_survive_interning() {
+        let pool = Pool::<u64, u32>::new();
+        let first = pool.intern(&0).expect("failed to intern value");
+        let first_ref = pool.resolve(first).expect("failed to resolve value");
+
+        let syms: Vec<_> = (1..1000u64).map(|i| pool.intern(&i).expect("failed to intern value")).collect();
+        assert_eq!(1000, pool.len());
+
+        assert_eq!(0, *first_ref);
+        for (i, &sym) in syms.iter().enumerate() {
+            assert_eq!(Ok(&(i as u64 + 1)), pool.resolve(sym));
+        }
+        assert_eq!(Ok(first), pool.intern(&0));
+    }
+
+    #[test]
+    fn can_interleave_intern_and_resolve() {
+        let pool = Pool::<str, u16>::new();
+        let mut refs = Vec::new();
+        for i in 0..100 {
+            let sym = pool.intern(&format!("value {}", i)).expect("failed to intern value");
+            refs.push(pool.resolve(sym).expect("failed to resolve value"));
+        }
+        for (i, s) in refs.iter().enumerate() {
+            assert_eq!(format!("value {}", i), *s);
+        }
+        assert_eq!(refs, pool.values().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn lookup_does_not_intern() {
+        let pool = Pool::<str, u16>::new();
+        assert_eq!(None, pool.get("foo"));
+        assert!(pool.is_empty());
+
+        let foo = pool.intern("foo").expect("failed to intern value");
+        assert_eq!(Some(foo), pool.get("foo"));
+        assert!(pool.contains_symbol(foo));
+    }
+
+    #[test]
+    fn has_expected_capacity() {
+        let pool = Pool::<u16, u8>::new();
+        for i in 0u16..256 {
+            pool.intern(&i).expect("failed to intern value");
+        }
+        assert!(pool.is_full());
+        assert!(pool.intern(&123).is_ok());
+        assert!(pool.intern(&256).is_err());
+    }
+}
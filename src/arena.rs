@@ -0,0 +1,457 @@
+// Copyright (C) 2016-2017 Symtern Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-Apache
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or
+// distributed except according to those terms.
+//! String interner that packs interned values into large, contiguous chunks.
+// [Module documentation lives on the exported pool type, `ArenaPool`.]
+use std::hash::BuildHasher;
+use std::{cmp, iter, slice};
+
+use crate::traits::{Intern, Lookup, Resolve, ResolveUnchecked, Iterate, Len, Capacity, SymbolId};
+use crate::{core, Result, ErrorKind};
+use crate::core::{Bucket, DefaultBuildHasher, HashMap};
+use crate::sym::{Symbol as ISymbol, Pool as IPool};
+use crate::basic::Sym;
+
+/// Size, in bytes, of each chunk of string storage.  Strings longer than this
+/// are given a chunk of their own.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Location of a stored string within the pool's chunks.
+#[derive(Copy, Clone, Debug)]
+struct Span {
+    chunk: u32,
+    start: u32,
+    len: u32,
+}
+
+/// String interner that stores all interned strings in large shared chunks.
+///
+/// Where [`Pool<str, I>`] keeps a separately-allocated `String` for each
+/// interned value, `ArenaPool` copies the bytes of each value onto the end of
+/// the current chunk and records only a compact 12-byte span per symbol.
+/// When interning large numbers of short strings this greatly reduces both
+/// memory use and the number of allocations performed.
+///
+/// Resolution returns `&str` just as it does for `Pool`, and symbols use the
+/// same [`Sym`] type.
+///
+/// ```rust
+/// use symtern::prelude::*;
+/// use symtern::ArenaPool;
+///
+/// let mut pool = ArenaPool::<u32>::new();
+/// let foo = pool.intern("foo").expect("failed to intern a value");
+/// let bar = pool.intern("bar").expect("failed to intern a value");
+///
+/// assert!(foo != bar);
+/// assert_eq!(Ok("foo"), pool.resolve(foo));
+/// assert_eq!(Ok("bar"), pool.resolve(bar));
+/// ```
+///
+/// Individual strings are limited to `u32::MAX` bytes; attempting to intern
+/// a longer string produces an error of kind `ErrorKind::ValueTooLarge`.
+///
+/// [`Pool<str, I>`]: struct.Pool.html
+/// [`Sym`]: struct.Sym.html
+#[derive(Clone, Debug)]
+pub struct Pool<I = usize, S = DefaultBuildHasher>
+    where I: SymbolId
+{
+    ids_map: HashMap<u64, Bucket<I>>,
+    spans: Vec<Span>,
+    chunks: Vec<String>,
+    hash_builder: S,
+    #[cfg(debug_assertions)]
+    pool_id: usize
+}
+
+// (inherent impl)
+impl<I> Pool<I>
+    where I: SymbolId
+{
+    /// Create a new, empty `ArenaPool` instance.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Create a new, empty `ArenaPool` instance with space for at least
+    /// `capacity` values.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Pool::with_capacity_and_hasher(capacity, Default::default())
+    }
+}
+
+impl<I, S> Pool<I, S>
+    where I: SymbolId,
+          S: BuildHasher
+{
+    /// Create a new, empty `ArenaPool` instance that will use the given
+    /// hasher factory to hash interned values.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Pool::with_capacity_and_hasher(0, hash_builder)
+    }
+
+    /// Create a new, empty `ArenaPool` instance with space for at least
+    /// `capacity` values, that will use the given hasher factory to hash
+    /// interned values.
+    #[cfg(not(debug_assertions))]
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Pool{ids_map: HashMap::with_capacity_and_hasher(capacity, Default::default()),
+             spans: Vec::with_capacity(capacity),
+             chunks: Vec::new(),
+             hash_builder}
+    }
+
+    /// Create a new, empty `ArenaPool` instance with space for at least
+    /// `capacity` values, that will use the given hasher factory to hash
+    /// interned values.
+    #[cfg(debug_assertions)]
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Pool{ids_map: HashMap::with_capacity_and_hasher(capacity, Default::default()),
+             spans: Vec::with_capacity(capacity),
+             chunks: Vec::new(),
+             hash_builder,
+             pool_id: crate::sym::next_pool_id()}
+    }
+
+    /// Fetch a reference to the pool's hasher factory.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Fetch the string stored at the given index into `spans`.
+    fn get_str(&self, idx: usize) -> &str {
+        let span = self.spans[idx];
+        let start = span.start as usize;
+        &self.chunks[span.chunk as usize][start..(start + span.len as usize)]
+    }
+
+    /// Find the ID of a stored string equal to `value`, given the value's
+    /// hash.
+    fn find_id(&self, key: u64, value: &str) -> Option<I> {
+        self.ids_map.get(&key).and_then(|bucket| {
+            bucket.ids().iter().cloned().find(|id| {
+                let idx = id.to_usize().expect("Unexpected failure to convert symbol ID to usize");
+                self.get_str(idx) == value
+            })
+        })
+    }
+
+    /// Copy a string onto the end of the last chunk, starting a new chunk if
+    /// there is not enough room left in it.
+    ///
+    /// Chunks are never grown beyond their initial capacity, so the bytes of
+    /// a stored string never move once they have been written.
+    fn store(&mut self, value: &str) -> Result<Span> {
+        if value.len() > u32::MAX as usize {
+            return Err(ErrorKind::ValueTooLarge.into());
+        }
+
+        let has_room = match self.chunks.last() {
+            Some(chunk) => chunk.capacity() - chunk.len() >= value.len(),
+            None => false,
+        };
+        if ! has_room {
+            self.chunks.push(String::with_capacity(cmp::max(CHUNK_SIZE, value.len())));
+        }
+
+        let chunk_idx = self.chunks.len() - 1;
+        let chunk = &mut self.chunks[chunk_idx];
+        let start = chunk.len();
+        chunk.push_str(value);
+
+        // Every chunk is either at most `CHUNK_SIZE` bytes long or holds
+        // a single string, so `start` always fits in a `u32`.
+        Ok(Span{chunk: chunk_idx as u32, start: start as u32, len: value.len() as u32})
+    }
+}
+
+impl<I, S> Len for Pool<I, S>
+    where I: SymbolId
+{
+    /// Get the number of entries contained in the pool.
+    fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    /// Check if the pool is "empty", i.e. has zero stored values.
+    fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// Check if the number of interned symbols has reached the maximum allowed
+    /// for the pool's ID type.
+    fn is_full(&self) -> bool {
+        // Symbol IDs range from 0 to M, where M is given by `I::max_value()`;
+        // hence a pool containing N entries is full iff N == M + 1.
+        self.len() > I::max_value().to_usize().unwrap_or(usize::MAX)
+    }
+}
+
+impl<I, S> Capacity for Pool<I, S>
+    where I: SymbolId
+{
+    /// Get the number of values the pool can index without reallocating.
+    /// String data is stored in fixed-size chunks, which are allocated as
+    /// needed regardless of the reserved capacity.
+    fn capacity(&self) -> usize {
+        cmp::min(self.ids_map.capacity(), self.spans.capacity())
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.ids_map.reserve(additional);
+        self.spans.reserve(additional);
+    }
+
+    fn try_reserve(&mut self, additional: usize) -> Result<()> {
+        self.ids_map.try_reserve(additional).map_err(|_| ErrorKind::AllocationFailed)?;
+        self.spans.try_reserve(additional).map_err(|_| ErrorKind::AllocationFailed)?;
+        Ok(())
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.ids_map.shrink_to_fit();
+        self.spans.shrink_to_fit();
+        self.chunks.shrink_to_fit();
+        if let Some(chunk) = self.chunks.last_mut() {
+            chunk.shrink_to_fit();
+        }
+    }
+}
+
+impl<I, S> crate::sym::Pool for Pool<I, S>
+    where I: SymbolId
+{
+    type Symbol = Sym<I>;
+
+    #[cfg(debug_assertions)]
+    fn id(&self) -> crate::sym::PoolId {
+        self.pool_id
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn create_symbol(&self, id: <Self::Symbol as crate::sym::Symbol>::Id) -> Self::Symbol {
+        Sym::create(id)
+    }
+
+    #[cfg(debug_assertions)]
+    fn create_symbol(&self, id: <Self::Symbol as crate::sym::Symbol>::Id) -> Self::Symbol {
+        Sym::create(id, self.id())
+    }
+}
+
+// Default
+impl<I, S> Default for Pool<I, S>
+    where I: SymbolId,
+          S: BuildHasher + Default
+{
+    fn default() -> Self {
+        Pool::with_hasher(Default::default())
+    }
+}
+
+// Intern
+impl<I, S> Intern for &mut Pool<I, S>
+    where I: SymbolId,
+          S: BuildHasher,
+{
+    type Input = str;
+    type Symbol = Sym<I>;
+
+    fn intern(self, value: &Self::Input) -> Result<Self::Symbol> {
+        let key = core::hash(&self.hash_builder, value);
+        if let Some(id) = self.find_id(key, value) {
+            Ok(self.create_symbol(id))
+        } else if self.is_full() {
+            Err(ErrorKind::PoolOverflow.into())
+        } else {
+            let span = self.store(value)?;
+            self.spans.push(span);
+
+            // We do not expect this conversion to fail, since the condition in
+            // the previous branch (`is_full()`) checks if a new ID would be
+            // a representable value.
+            let id = I::from_usize(self.spans.len() - 1)
+                .expect("Unexpected failure to convert symbol ID from usize");
+            core::insert_id(&mut self.ids_map, key, id);
+
+            Ok(self.create_symbol(id))
+        }
+    }
+}
+
+// ----------------------------------------------------------------
+// Lookup
+impl<I, S> Lookup for &Pool<I, S>
+    where I: SymbolId,
+          S: BuildHasher,
+{
+    type Input = str;
+    type Symbol = Sym<I>;
+
+    fn get(self, value: &Self::Input) -> Option<Self::Symbol> {
+        let key = core::hash(&self.hash_builder, value);
+        self.find_id(key, value).map(|id| self.create_symbol(id))
+    }
+
+    fn contains_symbol(self, s: Self::Symbol) -> bool {
+        if is_foreign_symbol!(self, s) { return false; }
+        let idx = s.id().to_usize().expect("Unexpected failure to convert symbol ID to usize");
+        idx < self.spans.len()
+    }
+}
+
+// ----------------------------------------------------------------
+// Resolve
+impl<'a, I, S> Resolve for &'a Pool<I, S>
+    where I: SymbolId,
+          S: BuildHasher,
+{
+    type Input = Sym<I>;
+    type Output = &'a str;
+
+    fn resolve(self, s: Self::Input) -> Result<Self::Output> {
+        check_matching_pool!(self, s);
+        // We previously converted the ID _from_ a usize, so this conversion should _not_ fail.
+        let idx = s.id().to_usize().expect("Unexpected failure to convert symbol ID to usize");
+
+        if self.spans.len() > idx {
+            Ok(self.get_str(idx))
+        } else {
+            Err(ErrorKind::NoSuchSymbol.into())
+        }
+    }
+}
+
+impl<I, S> ResolveUnchecked for &Pool<I, S>
+    where I: SymbolId,
+          S: BuildHasher,
+{
+    unsafe fn resolve_unchecked(self, symbol: Self::Input) -> Self::Output {
+        let idx = symbol.id().to_usize().expect("Unexpected failure to convert symbol ID to usize");
+        let span = self.spans.get_unchecked(idx);
+        let start = span.start as usize;
+        self.chunks.get_unchecked(span.chunk as usize).get_unchecked(start..(start + span.len as usize))
+    }
+}
+
+// ----------------------------------------------------------------
+// Iteration
+
+/// Iterator over the symbols and values stored in an
+/// [`ArenaPool`](struct.ArenaPool.html), in order of increasing symbol ID.
+pub struct Iter<'a, I, S>
+    where I: 'a + SymbolId,
+          S: 'a
+{
+    pool: &'a Pool<I, S>,
+    iter: iter::Enumerate<slice::Iter<'a, Span>>,
+}
+
+impl<'a, I, S> Iterator for Iter<'a, I, S>
+    where I: SymbolId,
+          S: BuildHasher
+{
+    type Item = (Sym<I>, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pool = self.pool;
+        self.iter.next().map(|(idx, _)| {
+            let id = I::from_usize(idx).expect("Unexpected failure to convert symbol ID from usize");
+            (pool.create_symbol(id), pool.get_str(idx))
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, I, S> ExactSizeIterator for Iter<'a, I, S>
+    where I: SymbolId,
+          S: BuildHasher
+{}
+
+impl<'a, I, S> Iterate for &'a Pool<I, S>
+    where I: SymbolId,
+          S: BuildHasher
+{
+    type Symbol = Sym<I>;
+    type Output = &'a str;
+    type Iter = Iter<'a, I, S>;
+
+    fn iter(self) -> Self::Iter {
+        Iter{pool: self, iter: self.spans.iter().enumerate()}
+    }
+}
+
+impl<'a, I, S> IntoIterator for &'a Pool<I, S>
+    where I: SymbolId,
+          S: BuildHasher
+{
+    type Item = (Sym<I>, &'a str);
+    type IntoIter = Iter<'a, I, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Iterate::iter(self)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Pool, CHUNK_SIZE};
+    use crate::traits::*;
+
+    #[test]
+    fn resolve_returns_expected_results() {
+        let mut pool = Pool::<u16>::new();
+        let foo = pool.intern("foo").unwrap();
+        let bar = pool.intern("bar").unwrap();
+
+        assert!(foo != bar);
+        assert_eq!(Ok(foo), pool.intern("foo"));
+        assert_eq!(Ok("foo"), pool.resolve(foo));
+        assert_eq!(Ok("bar"), pool.resolve(bar));
+        assert_eq!(Some(bar), pool.get("bar"));
+        assert_eq!(2, pool.len());
+    }
+
+    /// Check that values are packed into shared chunks, and that values
+    /// spilling over into new chunks -- including those larger than a whole
+    /// chunk -- are stored correctly.
+    #[test]
+    fn packs_values_into_chunks() {
+        let mut pool = Pool::<u32>::new();
+        let strings: Vec<String> = (0..20_000).map(|i| format!("identifier_{}", i)).collect();
+        let syms: Vec<_> = strings.iter().map(|s| pool.intern(s).expect("failed to intern value")).collect();
+
+        let big = "x".repeat(CHUNK_SIZE * 2);
+        let big_sym = pool.intern(&big).expect("failed to intern large value");
+        let after = pool.intern("after").expect("failed to intern value");
+
+        assert!(pool.chunks.len() > 1);
+        assert!(pool.chunks.len() < strings.len() / 100);
+        for (s, &sym) in strings.iter().zip(syms.iter()) {
+            assert_eq!(Ok(&s[..]), pool.resolve(sym));
+        }
+        assert_eq!(Ok(&big[..]), pool.resolve(big_sym));
+        assert_eq!(Ok("after"), pool.resolve(after));
+        assert_eq!(strings.len() + 2, pool.iter().count());
+    }
+
+    #[test]
+    fn has_expected_capacity() {
+        let mut pool = Pool::<u8>::new();
+        for i in 0..256 {
+            pool.intern(&i.to_string()).expect("failed to intern value");
+        }
+        assert!(pool.is_full());
+        assert!(pool.intern("123").is_ok());
+        assert!(pool.intern("1234").is_err());
+    }
+}
@@ -7,25 +7,25 @@
 // distributed except according to those terms.
 //! Basic hash-based generic interner.
 
-use std::hash::Hash;
+use std::hash::{BuildHasher, Hash};
 use std::borrow::{Borrow, ToOwned};
-#[cfg(debug_assertions)] use std::sync::atomic::{self, AtomicUsize, Ordering};
+use std::{cmp, iter, mem, slice};
+use std::iter::FromIterator;
+use std::ops::Index;
+use std::io::{self, Read, Write};
+use std::sync::OnceLock;
+use std::any::Any;
+use std::fmt;
 
-use crate::traits::{Intern, Resolve, ResolveUnchecked, Len, SymbolId};
-use crate::{core, Result, ErrorKind};
+use crate::traits::{Intern, Lookup, Resolve, ResolveUnchecked, Iterate, Len, Capacity, Clear, Stats, PoolStats, SymbolId};
+use crate::{core, snapshot, Result, ErrorKind};
+use crate::core::{Bucket, DefaultBuildHasher, HashMap};
 use crate::sym::{Symbol as ISymbol, Pool as IPool};
+use crate::frozen::Pool as FrozenPool;
+use crate::remap::SymbolRemap;
+use crate::fmt::DebugResolver;
 
 
-#[cfg(debug_assertions)]
-static NEXT_POOL_ID: AtomicUsize = atomic::AtomicUsize::new(0);
-
-
-#[cfg(feature = "fnv")]
-type HashMap<K, V> = ::fnv::FnvHashMap<K, V>;
-
-#[cfg(not(feature = "fnv"))]
-type HashMap<K, V> = ::std::collections::HashMap<K, V>;
-
 make_sym! {
     pub Sym<I>:
     "Symbol type used by [`Pool`](struct.Pool.html)'s [`Intern`](../traits/trait.Intern.html) and [`Resolve`](../traits/trait.Resolve.html) implementations.";
@@ -49,33 +49,59 @@ make_sym! {
 /// let mut pool = Pool::<_,u8>::new();
 /// assert!(pool.intern(&WibbleWobble{whee: vec![1, 2, 3, 4, 5]}).is_ok());
 /// ```
+///
+/// Values are hashed using the `BuildHasher` given by the type parameter `S`,
+/// which defaults to [`DefaultBuildHasher`].  Pools that may be fed
+/// adversarial input can instead use a randomly-seeded hasher like
+/// `std::collections::hash_map::RandomState`:
+///
+/// ```rust
+/// use std::collections::hash_map::RandomState;
+/// use symtern::prelude::*;
+/// use symtern::Pool;
+///
+/// let mut pool = Pool::<str, u32, RandomState>::with_hasher(RandomState::new());
+/// let sym = pool.intern("untrusted input").expect("failed to intern a value");
+/// assert_eq!(Ok("untrusted input"), pool.resolve(sym));
+/// ```
+///
+/// [`DefaultBuildHasher`]: type.DefaultBuildHasher.html
 #[derive(Debug)]
-pub struct Pool<T: ?Sized, I = usize>
+pub struct Pool<T: ?Sized, I = usize, S = DefaultBuildHasher>
     where T: ToOwned + Eq + Hash,
           T::Owned: Eq + Hash,
           I: SymbolId
 {
-    ids_map: HashMap<u64, I>,
+    ids_map: HashMap<u64, Bucket<I>>,
     lookup_vec: Vec<T::Owned>,
+    /// Position of each value in sorted order, indexed by symbol ID.  This is
+    /// computed when first needed, and discarded whenever values change.
+    ranks: OnceLock<Box<[I]>>,
+    hash_builder: S,
     #[cfg(debug_assertions)]
     pool_id: usize
 }
 
-impl<T: ?Sized, I> Clone for Pool<T, I>
+impl<T: ?Sized, I, S> Clone for Pool<T, I, S>
     where T: ToOwned + Eq + Hash,
           T::Owned: Eq + Hash + Clone,
           I: SymbolId,
+          S: Clone,
 {
     #[cfg(debug_assertions)]
     fn clone(&self) -> Self {
         Pool{ids_map: self.ids_map.clone(),
              lookup_vec: self.lookup_vec.clone(),
+             ranks: self.ranks.clone(),
+             hash_builder: self.hash_builder.clone(),
              pool_id: self.pool_id}
     }
     #[cfg(not(debug_assertions))]
     fn clone(&self) -> Self {
         Pool{ids_map: self.ids_map.clone(),
-             lookup_vec: self.lookup_vec.clone()}
+             lookup_vec: self.lookup_vec.clone(),
+             ranks: self.ranks.clone(),
+             hash_builder: self.hash_builder.clone()}
     }
 }
 
@@ -89,9 +115,489 @@ impl<T: ?Sized, I> Pool<T, I>
     pub fn new() -> Self {
         Default::default()
     }
+
+    /// Create a new, empty `Pool` instance with space for at least
+    /// `capacity` values.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Pool::with_capacity_and_hasher(capacity, Default::default())
+    }
+}
+
+impl<T: ?Sized, I, S> Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId,
+          S: BuildHasher
+{
+    /// Create a new, empty `Pool` instance that will use the given hasher
+    /// factory to hash interned values.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Pool::with_capacity_and_hasher(0, hash_builder)
+    }
+
+    /// Create a new, empty `Pool` instance with space for at least
+    /// `capacity` values, that will use the given hasher factory to hash
+    /// interned values.
+    #[cfg(not(debug_assertions))]
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Pool{ids_map: HashMap::with_capacity_and_hasher(capacity, Default::default()),
+             lookup_vec: Vec::with_capacity(capacity),
+             ranks: OnceLock::new(),
+             hash_builder}
+    }
+
+    /// Create a new, empty `Pool` instance with space for at least
+    /// `capacity` values, that will use the given hasher factory to hash
+    /// interned values.
+    #[cfg(debug_assertions)]
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Pool{ids_map: HashMap::with_capacity_and_hasher(capacity, Default::default()),
+             lookup_vec: Vec::with_capacity(capacity),
+             ranks: OnceLock::new(),
+             hash_builder,
+             pool_id: crate::sym::next_pool_id()}
+    }
+
+    /// Fetch a reference to the pool's hasher factory.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Convert the pool into an immutable [`FrozenPool`] that looks up values
+    /// using a minimal perfect hash.  Every value keeps its symbol ID, so
+    /// symbols obtained from this pool can be resolved on the frozen pool.
+    ///
+    /// [`FrozenPool`]: struct.FrozenPool.html
+    pub fn freeze(mut self) -> FrozenPool<T, I, S>
+        where T::Owned: Borrow<T>
+    {
+        self.ids_map = HashMap::default();
+        FrozenPool::new(self)
+    }
+
+    /// Move every value from another pool into this one, and return a table
+    /// that translates the other pool's symbols into this pool's.
+    ///
+    /// Values already present in this pool keep their existing symbols; the
+    /// rest are given new IDs in the order of their IDs in `other`.  Fails
+    /// with `ErrorKind::PoolOverflow`, without changing this pool, if there
+    /// are too many new values to fit.
+    ///
+    /// ```rust
+    /// use symtern::prelude::*;
+    /// use symtern::Pool;
+    ///
+    /// let mut global = Pool::<str, u32>::new();
+    /// global.intern("main").expect("failed to intern a value");
+    ///
+    /// let mut file = Pool::<str, u32>::new();
+    /// let mut syms = vec![file.intern("helper").expect("failed to intern a value"),
+    ///                     file.intern("main").expect("failed to intern a value")];
+    ///
+    /// let remap = global.merge(file).expect("failed to merge pools");
+    /// remap.apply(&mut syms).expect("failed to remap symbols");
+    /// assert_eq!(Ok("helper"), global.resolve(syms[0]));
+    /// assert_eq!(global.get("main"), Some(syms[1]));
+    /// ```
+    pub fn merge<S2>(&mut self, mut other: Pool<T, I, S2>) -> Result<SymbolRemap<I>>
+        where T::Owned: Borrow<T>
+    {
+        // Find each value's hash and existing ID before changing anything, so
+        // that we can check for overflow up front.
+        let found: Vec<(u64, Option<I>)> = other.lookup_vec.iter().map(|value| {
+            let key = core::hash(&self.hash_builder, value.borrow());
+            (key, self.find_id(key, value.borrow()))
+        }).collect();
+        let added = found.iter().filter(|&&(_, id)| id.is_none()).count();
+        let max = I::max_value().to_usize().unwrap_or(usize::MAX);
+        if added > 0 && (self.lookup_vec.len() + added - 1) > max {
+            return Err(ErrorKind::PoolOverflow.into());
+        }
+
+        if added > 0 {
+            self.ranks.take();
+        }
+        self.lookup_vec.reserve(added);
+        self.ids_map.reserve(added);
+        let values = mem::take(&mut other.lookup_vec);
+        let ids = values.into_iter().zip(found).map(|(value, (key, existing))| {
+            Some(existing.unwrap_or_else(|| {
+                self.lookup_vec.push(value);
+                let id = I::from_usize(self.lookup_vec.len() - 1)
+                    .expect("Unexpected failure to convert symbol ID from usize");
+                core::insert_id(&mut self.ids_map, key, id);
+                id
+            }))
+        }).collect();
+
+        #[cfg(debug_assertions)]
+        let remap = SymbolRemap::new(ids, other.id(), self.id());
+        #[cfg(not(debug_assertions))]
+        let remap = SymbolRemap::new(ids);
+        Ok(remap)
+    }
+
+    /// Remove every value for which `keep` returns `false`, and return
+    /// a table that translates the symbols of the remaining values.
+    ///
+    /// The remaining values are renumbered, in their original order, so that
+    /// symbol IDs stay dense; symbols created before the call must be passed
+    /// through the returned remap before they are used again.  In debug
+    /// builds the pool is also given a new identity, so that resolving a
+    /// symbol that was not remapped will panic.
+    ///
+    /// ```rust
+    /// use symtern::prelude::*;
+    /// use symtern::Pool;
+    ///
+    /// let mut pool = Pool::<str, u32>::new();
+    /// let tmp = pool.intern("tmp0").expect("failed to intern a value");
+    /// let main = pool.intern("main").expect("failed to intern a value");
+    ///
+    /// let remap = pool.retain(|_, value| ! value.starts_with("tmp"));
+    /// assert_eq!(None, remap.get(tmp));
+    /// let main = remap.get(main).expect("retained value is missing from remap");
+    /// assert_eq!(Ok("main"), pool.resolve(main));
+    /// assert_eq!(1, pool.len());
+    /// ```
+    pub fn retain<F>(&mut self, mut keep: F) -> SymbolRemap<I>
+        where F: FnMut(Sym<I>, &T) -> bool,
+              T::Owned: Borrow<T>
+    {
+        let mut next = 0;
+        let ids: Vec<Option<I>> = self.lookup_vec.iter().enumerate().map(|(idx, value)| {
+            let id = I::from_usize(idx).expect("Unexpected failure to convert symbol ID from usize");
+            if keep(self.create_symbol(id), value.borrow()) {
+                next += 1;
+                Some(I::from_usize(next - 1).expect("Unexpected failure to convert symbol ID from usize"))
+            } else {
+                None
+            }
+        }).collect();
+
+        let mut kept = ids.iter().map(Option::is_some);
+        self.lookup_vec.retain(|_| kept.next().expect("Unexpected end of retained-value flags"));
+        self.ranks.take();
+        self.rebuild_index();
+
+        #[cfg(debug_assertions)]
+        let remap = {
+            let from_pool = mem::replace(&mut self.pool_id, crate::sym::next_pool_id());
+            SymbolRemap::new(ids, from_pool, self.pool_id)
+        };
+        #[cfg(not(debug_assertions))]
+        let remap = SymbolRemap::new(ids);
+        remap
+    }
+
+    /// Find the ID of a stored value equal to `value`, given the value's hash.
+    ///
+    /// Values that merely share a hash are told apart by comparing them
+    /// against the stored copies, so a hash collision can never cause two
+    /// distinct values to be given the same symbol.
+    fn find_id(&self, key: u64, value: &T) -> Option<I>
+        where T::Owned: Borrow<T>
+    {
+        self.ids_map.get(&key).and_then(|bucket| {
+            bucket.ids().iter().cloned().find(|id| {
+                let idx = id.to_usize().expect("Unexpected failure to convert symbol ID to usize");
+                self.lookup_vec[idx].borrow() == value
+            })
+        })
+    }
+
+    /// Add a value to the pool as the next symbol ID, unless an equal value
+    /// is already present.  Returns `Ok(None)` in the latter case.
+    ///
+    /// This is used to rebuild pools from serialized values, where an existing
+    /// value indicates invalid input rather than a successful interning.
+    fn push_distinct(&mut self, value: T::Owned) -> Result<Option<I>>
+        where T::Owned: Borrow<T>
+    {
+        let key = core::hash(&self.hash_builder, value.borrow());
+        if self.find_id(key, value.borrow()).is_some() {
+            return Ok(None)
+        } else if self.is_full() {
+            return Err(ErrorKind::PoolOverflow.into())
+        }
+        self.lookup_vec.push(value);
+        self.ranks.take();
+        let id = I::from_usize(self.lookup_vec.len() - 1)
+            .expect("Unexpected failure to convert symbol ID from usize");
+        core::insert_id(&mut self.ids_map, key, id);
+        Ok(Some(id))
+    }
+
+    /// Rebuild the index used to look up values' symbol IDs from scratch.
+    pub(crate) fn rebuild_index(&mut self)
+        where T::Owned: Borrow<T>
+    {
+        let mut ids_map = HashMap::default();
+        ids_map.reserve(self.lookup_vec.len());
+        for (idx, value) in self.lookup_vec.iter().enumerate() {
+            let id = I::from_usize(idx).expect("Unexpected failure to convert symbol ID from usize");
+            core::insert_id(&mut ids_map, core::hash(&self.hash_builder, value.borrow()), id);
+        }
+        self.ids_map = ids_map;
+    }
+}
+
+impl<T: ?Sized, I, S> Pool<T, I, S>
+    where T: ToOwned + Ord + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId + Ord,
+          S: BuildHasher
+{
+    /// Compute the position of each value in sorted order.
+    fn compute_ranks(&self) -> Box<[I]> {
+        let mut order: Vec<usize> = (0..self.lookup_vec.len()).collect();
+        order.sort_unstable_by(|&a, &b| self.lookup_vec[a].borrow().cmp(self.lookup_vec[b].borrow()));
+
+        let mut ranks = vec![I::max_value(); order.len()];
+        for (rank, idx) in order.into_iter().enumerate() {
+            ranks[idx] = I::from_usize(rank).expect("Unexpected failure to convert symbol ID from usize");
+        }
+        ranks.into_boxed_slice()
+    }
+
+    /// Fetch the position of a symbol's value in sorted order.
+    fn rank(&self, s: Sym<I>) -> I {
+        check_matching_pool!(self, s);
+        let idx = s.id.to_usize().expect("Unexpected failure to convert symbol ID to usize");
+        self.ranks.get_or_init(|| self.compute_ranks())[idx]
+    }
+
+    /// Compare the values represented by two symbols.
+    ///
+    /// The first comparison after values are added to the pool ranks every
+    /// value in sorted order; later comparisons only look up the symbols'
+    /// ranks, and never compare values.  Panics if either symbol was not
+    /// created by this pool.
+    ///
+    /// ```rust
+    /// use std::cmp::Ordering;
+    /// use symtern::prelude::*;
+    /// use symtern::Pool;
+    ///
+    /// let mut pool = Pool::<str, u32>::new();
+    /// let zebra = pool.intern("zebra").expect("failed to intern a value");
+    /// let aardvark = pool.intern("aardvark").expect("failed to intern a value");
+    /// assert_eq!(Ordering::Greater, pool.cmp_values(zebra, aardvark));
+    /// ```
+    pub fn cmp_values(&self, a: Sym<I>, b: Sym<I>) -> cmp::Ordering {
+        self.rank(a).cmp(&self.rank(b))
+    }
+
+    /// Sort a slice of symbols by the values they represent.  Like
+    /// [`cmp_values`](#method.cmp_values), this compares precomputed ranks
+    /// instead of values.
+    pub fn sort_by_value(&self, symbols: &mut [Sym<I>]) {
+        symbols.sort_unstable_by_key(|&s| self.rank(s));
+    }
+
+    /// Renumber the pool's values in sorted order, and return a table that
+    /// translates the symbols created before the call.
+    ///
+    /// Once sorted, comparing two of the pool's symbols gives the same result
+    /// as comparing their values, until the next new value is interned.  As
+    /// with [`retain`](#method.retain), in debug builds the pool is given
+    /// a new identity so that resolving a symbol that was not remapped will
+    /// panic.
+    ///
+    /// ```rust
+    /// use symtern::prelude::*;
+    /// use symtern::Pool;
+    ///
+    /// let mut pool: Pool<str, u32> = ["pear", "apple", "fig"].iter().cloned().collect();
+    /// pool.sort_values();
+    /// let apple = pool.get("apple").expect("value is missing from pool");
+    /// let fig = pool.get("fig").expect("value is missing from pool");
+    /// assert!(apple < fig);
+    /// ```
+    pub fn sort_values(&mut self) -> SymbolRemap<I> {
+        let ranks = self.ranks.take().unwrap_or_else(|| self.compute_ranks());
+        let mut values: Vec<(I, T::Owned)> = ranks.iter().cloned().zip(mem::take(&mut self.lookup_vec)).collect();
+        values.sort_unstable_by_key(|v| v.0);
+        self.lookup_vec = values.into_iter().map(|(_, value)| value).collect();
+        self.rebuild_index();
+
+        let ids = ranks.iter().cloned().map(Some).collect();
+        #[cfg(debug_assertions)]
+        let remap = {
+            let from_pool = mem::replace(&mut self.pool_id, crate::sym::next_pool_id());
+            SymbolRemap::new(ids, from_pool, self.pool_id)
+        };
+        #[cfg(not(debug_assertions))]
+        let remap = SymbolRemap::new(ids);
+        remap
+    }
+
+    /// Convert the pool into a [`FrozenPool`] whose symbol IDs follow the
+    /// sorted order of their values, so that comparing symbols compares
+    /// their values.  Returns the frozen pool along with a table that
+    /// translates this pool's symbols.
+    ///
+    /// [`FrozenPool`]: struct.FrozenPool.html
+    pub fn freeze_sorted(mut self) -> (FrozenPool<T, I, S>, SymbolRemap<I>) {
+        let remap = self.sort_values();
+        (self.freeze(), remap)
+    }
+}
+
+/// Add a value read from a snapshot to a pool, rejecting duplicate values.
+fn push_snapshot_value<T, I, S>(pool: &mut Pool<T, I, S>, value: T::Owned) -> Result<()>
+    where T: ?Sized + ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId,
+          S: BuildHasher
+{
+    match pool.push_distinct(value)? {
+        Some(_) => Ok(()),
+        None => Err(ErrorKind::CorruptSnapshot.into()),
+    }
+}
+
+impl<I, S> Pool<str, I, S>
+    where I: SymbolId,
+          S: BuildHasher
+{
+    /// Write a snapshot of the pool's contents in Symtern's compact binary
+    /// format.  The snapshot records the size of the pool's symbol-ID type,
+    /// and each value's symbol ID is preserved when the snapshot is loaded
+    /// with [`read_snapshot`].
+    ///
+    /// Many small writes are made to `writer`, so you may want to wrap it in
+    /// a `BufWriter`.
+    ///
+    /// ```rust
+    /// use symtern::prelude::*;
+    /// use symtern::Pool;
+    ///
+    /// let mut pool = Pool::<str, u32>::new();
+    /// pool.intern("persistent").expect("failed to intern a value");
+    ///
+    /// let mut snapshot = Vec::new();
+    /// pool.write_snapshot(&mut snapshot).expect("failed to write snapshot");
+    ///
+    /// let loaded = Pool::<str, u32>::read_snapshot(&snapshot[..]).expect("failed to read snapshot");
+    /// assert_eq!(pool.len(), loaded.len());
+    ///
+    /// // Symbols from the original pool belong to that pool, but the value
+    /// // keeps its ID in the loaded one.
+    /// assert_eq!(Some("persistent"), loaded.values().nth(0));
+    /// ```
+    ///
+    /// [`read_snapshot`]: #method.read_snapshot
+    pub fn write_snapshot<W: ?Sized + Write>(&self, writer: &mut W) -> io::Result<()> {
+        snapshot::write::<I, _, _>(writer, self.lookup_vec.len(), self.lookup_vec.iter().map(String::as_bytes))
+    }
+
+    /// Load a pool from a snapshot written by [`write_snapshot`].
+    ///
+    /// Fails with `ErrorKind::IdWidthMismatch` if the snapshot was written by
+    /// a pool with a different symbol-ID size, `ErrorKind::TruncatedSnapshot`
+    /// if the snapshot ends early, and `ErrorKind::CorruptSnapshot` if its
+    /// contents are otherwise invalid.
+    ///
+    /// [`write_snapshot`]: #method.write_snapshot
+    pub fn read_snapshot<R: Read>(reader: R) -> Result<Self>
+        where S: Default
+    {
+        let mut pool = Pool::with_hasher(S::default());
+        snapshot::read::<I, _, _>(reader, |value| {
+            let value = String::from_utf8(value).map_err(|_| ErrorKind::CorruptSnapshot)?;
+            push_snapshot_value(&mut pool, value)
+        })?;
+        Ok(pool)
+    }
+}
+
+impl<I, S> Pool<[u8], I, S>
+    where I: SymbolId,
+          S: BuildHasher
+{
+    /// Write a snapshot of the pool's contents in Symtern's compact binary
+    /// format.  The snapshot records the size of the pool's symbol-ID type,
+    /// and each value's symbol ID is preserved when the snapshot is loaded
+    /// with [`read_snapshot`].
+    ///
+    /// Many small writes are made to `writer`, so you may want to wrap it in
+    /// a `BufWriter`.
+    ///
+    /// [`read_snapshot`]: #method.read_snapshot
+    pub fn write_snapshot<W: ?Sized + Write>(&self, writer: &mut W) -> io::Result<()> {
+        snapshot::write::<I, _, _>(writer, self.lookup_vec.len(), self.lookup_vec.iter().map(Vec::as_slice))
+    }
+
+    /// Load a pool from a snapshot written by [`write_snapshot`].
+    ///
+    /// Fails with `ErrorKind::IdWidthMismatch` if the snapshot was written by
+    /// a pool with a different symbol-ID size, `ErrorKind::TruncatedSnapshot`
+    /// if the snapshot ends early, and `ErrorKind::CorruptSnapshot` if its
+    /// contents are otherwise invalid.
+    ///
+    /// [`write_snapshot`]: #method.write_snapshot
+    pub fn read_snapshot<R: Read>(reader: R) -> Result<Self>
+        where S: Default
+    {
+        let mut pool = Pool::with_hasher(S::default());
+        snapshot::read::<I, _, _>(reader, |value| push_snapshot_value(&mut pool, value))?;
+        Ok(pool)
+    }
+}
+
+impl<T: ?Sized, I, S> Stats for Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId
+{
+    /// Fetch the pool's statistics.  The index's size is estimated from its
+    /// capacity, as one entry and a one-byte control tag per usable slot;
+    /// since hash tables keep some slots free, this is a lower bound.
+    fn stats(&self) -> PoolStats {
+        let mut collisions = 0;
+        let mut bucket_bytes = 0;
+        for bucket in self.ids_map.values() {
+            if let Bucket::Many(ref ids) = *bucket {
+                collisions += ids.len() - 1;
+                bucket_bytes += ids.capacity() * mem::size_of::<I>();
+            }
+        }
+        let index_bytes = self.ids_map.capacity() * (mem::size_of::<(u64, Bucket<I>)>() + 1);
+        let table_bytes = self.lookup_vec.capacity() * mem::size_of::<T::Owned>();
+        let rank_bytes = self.ranks.get().map_or(0, |ranks| ranks.len() * mem::size_of::<I>());
+
+        PoolStats{len: self.lookup_vec.len(),
+                  data_bytes: self.lookup_vec.iter().map(|value| mem::size_of_val(value.borrow())).sum(),
+                  overhead_bytes: index_bytes + bucket_bytes + table_bytes + rank_bytes,
+                  load_factor: if self.ids_map.capacity() == 0 { 0.0 }
+                               else { self.ids_map.len() as f64 / self.ids_map.capacity() as f64 },
+                  collisions,
+                  ..PoolStats::default()}
+    }
 }
 
-impl<'a, T: ?Sized, I> Len for Pool<T, I>
+impl<T: ?Sized, I, S> DebugResolver for Pool<T, I, S>
+    where T: ToOwned + Eq + Hash + fmt::Debug,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId + 'static
+{
+    fn with_debug_value(&self, symbol: &dyn Any, f: &mut dyn FnMut(&dyn fmt::Debug) -> fmt::Result) -> Option<fmt::Result> {
+        let s = symbol.downcast_ref::<Sym<I>>()?;
+        // Release builds have no pool IDs, so there any in-range symbol of
+        // this type is taken to be ours.
+        #[cfg(debug_assertions)]
+        {
+            if s.pool_id() != self.id() {
+                return None;
+            }
+        }
+        let idx = s.id.to_usize()?;
+        self.lookup_vec.get(idx).map(|value| f(&value.borrow()))
+    }
+}
+
+impl<'a, T: ?Sized, I, S> Len for Pool<T, I, S>
     where T: ToOwned + Eq + Hash,
           T::Owned: Eq + Hash,
           I: SymbolId
@@ -112,11 +618,57 @@ impl<'a, T: ?Sized, I> Len for Pool<T, I>
         // Symbol IDs range from 0 to M, where M is given by `I::max_value()`;
         // hence a pool containing N entries is full iff N == M + 1.
         let len = self.len();
-        len >= 1 && len - 1 >= I::max_value().to_usize().expect("Unexpected failure to convert index type `max_value()` result to usize")
+        len >= 1 && len - 1 >= I::max_value().to_usize().unwrap_or(usize::MAX)
     }
 }
 
-impl<'a, T: ?Sized, I> crate::sym::Pool for Pool<T, I>
+impl<T: ?Sized, I, S> Capacity for Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    /// Get the number of values the pool can hold without reallocating.
+    fn capacity(&self) -> usize {
+        cmp::min(self.ids_map.capacity(), self.lookup_vec.capacity())
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.ids_map.reserve(additional);
+        self.lookup_vec.reserve(additional);
+    }
+
+    fn try_reserve(&mut self, additional: usize) -> Result<()> {
+        self.ids_map.try_reserve(additional).map_err(|_| ErrorKind::AllocationFailed)?;
+        self.lookup_vec.try_reserve(additional).map_err(|_| ErrorKind::AllocationFailed)?;
+        Ok(())
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.ids_map.shrink_to_fit();
+        self.lookup_vec.shrink_to_fit();
+    }
+}
+
+impl<T: ?Sized, I, S> Clear for Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    /// Remove all values from the pool, keeping its allocated capacity.  In
+    /// debug builds the pool is also given a new identity, so that resolving
+    /// a symbol created before the clear will panic.
+    fn clear(&mut self) {
+        self.ids_map.clear();
+        self.lookup_vec.clear();
+        self.ranks.take();
+        #[cfg(debug_assertions)]
+        {
+            self.pool_id = crate::sym::next_pool_id();
+        }
+    }
+}
+
+impl<'a, T: ?Sized, I, S> crate::sym::Pool for Pool<T, I, S>
     where T: ToOwned + Eq + Hash,
           T::Owned: Eq + Hash,
           I: SymbolId
@@ -129,7 +681,7 @@ impl<'a, T: ?Sized, I> crate::sym::Pool for Pool<T, I>
     }
 
     #[cfg(not(debug_assertions))]
-    fn create_symbol(&self, id: <Self::Symbol as ::sym::Symbol>::Id) -> Self::Symbol {
+    fn create_symbol(&self, id: <Self::Symbol as crate::sym::Symbol>::Id) -> Self::Symbol {
         Sym::create(id)
     }
 
@@ -140,78 +692,80 @@ impl<'a, T: ?Sized, I> crate::sym::Pool for Pool<T, I>
 }
 
 // Default
-impl<T: ?Sized, I> Default for Pool<T, I>
+impl<T: ?Sized, I, S> Default for Pool<T, I, S>
     where T: ToOwned + Eq + Hash,
           T::Owned: Eq + Hash,
-          I: SymbolId
+          I: SymbolId,
+          S: BuildHasher + Default
 {
-    #[cfg(not(debug_assertions))]
-    fn default() -> Self {
-        Pool{ids_map: Default::default(),
-             lookup_vec: Default::default()}
-    }
-    #[cfg(debug_assertions)]
     fn default() -> Self {
-        Pool{ids_map: Default::default(),
-             lookup_vec: Default::default(),
-             pool_id: NEXT_POOL_ID.fetch_add(1, Ordering::SeqCst)}
+        Pool::with_hasher(Default::default())
     }
 }
 
 // Intern
-impl<'a, T: ?Sized, I> Intern for &'a mut Pool<T, I>
+impl<'a, T: ?Sized, I, S> Intern for &'a mut Pool<T, I, S>
     where I: SymbolId,
           T: ToOwned + Eq + Hash,
           T::Owned: Eq + Hash + Borrow<T>,
+          S: BuildHasher,
 {
     type Input = T;
     type Symbol = Sym<I>;
 
     fn intern(self, value: &Self::Input) -> Result<Self::Symbol> {
-        let key = core::hash::<T, core::DefaultHashAlgo>(value);
-        if let Some(&id) = self.ids_map.get(&key) {
+        let key = core::hash(&self.hash_builder, value);
+        if let Some(id) = self.find_id(key, value) {
             return Ok(self.create_symbol(id))
         } else if self.is_full() {
             return Err(ErrorKind::PoolOverflow.into())
         } else {
             self.lookup_vec.push(value.to_owned());
+            self.ranks.take();
 
             // We do not expect this conversion to fail, since the condition in
             // the previous branch (`is_full()`) checks if a new ID would be
             // a representable value.
             let id = I::from_usize(self.lookup_vec.len() - 1)
                 .expect("Unexpected failure to convert symbol ID from usize");
-            self.ids_map.insert(key, id);
+            core::insert_id(&mut self.ids_map, key, id);
 
             Ok(self.create_symbol(id))
         }
     }
 }
 
-#[cfg(debug_assertions)]
-macro_rules! check_matching_pool {
-    ($slf: ident, $sym: ident) => {
-        if $sym.pool_id() != $slf.id() {
-            panic!(concat!("\nDetected an invalid attempt to resolve a symbol on a pool that did not\n",
-                           "create it.  This is a bug in the program or library using Symtern; do not\n",
-                           "report it to the Symtern developers."));
-        }
-    };
-}
+// ----------------------------------------------------------------
+// Lookup
+impl<T: ?Sized, I, S> Lookup for &Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId,
+          S: BuildHasher,
+{
+    type Input = T;
+    type Symbol = Sym<I>;
+
+    fn get(self, value: &Self::Input) -> Option<Self::Symbol> {
+        let key = core::hash(&self.hash_builder, value);
+        self.find_id(key, value).map(|id| self.create_symbol(id))
+    }
 
-#[cfg(not(debug_assertions))]
-macro_rules! check_matching_pool {
-    ($slf: ident, $sym: ident) => {};
+    fn contains_symbol(self, s: Self::Symbol) -> bool {
+        if is_foreign_symbol!(self, s) { return false; }
+        let idx = s.id().to_usize().expect("Unexpected failure to convert symbol ID to usize");
+        idx < self.lookup_vec.len()
+    }
 }
 
 // ----------------------------------------------------------------
 // Resolve
-impl<'a,T: ?Sized, I> Resolve for &'a Pool<T, I>
+impl<'a,T: ?Sized, I, S> Resolve for &'a Pool<T, I, S>
     where T: ToOwned + Eq + Hash,
           T::Owned: Eq + Hash + Borrow<T>,
           I: SymbolId
 {
-    type Input = <&'a mut Pool<T, I> as Intern>::Symbol;
+    type Input = Sym<I>;
     type Output = &'a T;
 
     fn resolve(self, s: Self::Input) -> Result<Self::Output> {
@@ -226,7 +780,7 @@ impl<'a,T: ?Sized, I> Resolve for &'a Pool<T, I>
         }
     }
 }
-impl<'a, T: ?Sized, I> ResolveUnchecked for &'a Pool<T, I>
+impl<'a, T: ?Sized, I, S> ResolveUnchecked for &'a Pool<T, I, S>
     where T: ToOwned + Eq + Hash,
           T::Owned: Eq + Hash + Borrow<T>,
           I: SymbolId
@@ -238,12 +792,626 @@ impl<'a, T: ?Sized, I> ResolveUnchecked for &'a Pool<T, I>
 }
 
 
+impl<T: ?Sized, I, S> Index<Sym<I>> for Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId
+{
+    type Output = T;
+
+    /// Fetch the value represented by a symbol.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the symbol cannot be resolved by the pool.
+    fn index(&self, s: Sym<I>) -> &T {
+        match self.resolve(s) {
+            Ok(value) => value,
+            Err(e) => panic!("failed to resolve symbol: {}", e),
+        }
+    }
+}
+
+// ----------------------------------------------------------------
+// Iteration
+
+/// Iterator over the symbols and values stored in a [`Pool`], in order of
+/// increasing symbol ID.
+///
+/// [`Pool`]: struct.Pool.html
+pub struct Iter<'a, T: ?Sized, I, S>
+    where T: 'a + ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: 'a + SymbolId,
+          S: 'a
+{
+    pool: &'a Pool<T, I, S>,
+    iter: iter::Enumerate<slice::Iter<'a, T::Owned>>,
+}
+
+impl<'a, T: ?Sized, I, S> Iterator for Iter<'a, T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId
+{
+    type Item = (Sym<I>, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|(idx, value)| {
+            let id = I::from_usize(idx).expect("Unexpected failure to convert symbol ID from usize");
+            (self.pool.create_symbol(id), value.borrow())
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, T: ?Sized, I, S> ExactSizeIterator for Iter<'a, T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId
+{}
+
+impl<'a, T: ?Sized, I, S> Iterate for &'a Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId
+{
+    type Symbol = Sym<I>;
+    type Output = &'a T;
+    type Iter = Iter<'a, T, I, S>;
+
+    fn iter(self) -> Self::Iter {
+        Iter{pool: self, iter: self.lookup_vec.iter().enumerate()}
+    }
+}
+
+impl<'a, T: ?Sized, I, S> IntoIterator for &'a Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId
+{
+    type Item = (Sym<I>, &'a T);
+    type IntoIter = Iter<'a, T, I, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Iterate::iter(self)
+    }
+}
+
+impl<'a, T: ?Sized, I, S> Extend<&'a T> for Pool<T, I, S>
+    where T: 'a + ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId,
+          S: BuildHasher
+{
+    /// Intern each value produced by an iterator.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pool overflows; use [`intern`] directly if you need to
+    /// handle this condition.
+    ///
+    /// [`intern`]: traits/trait.Intern.html#tymethod.intern
+    fn extend<It: IntoIterator<Item=&'a T>>(&mut self, iter: It) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for value in iter {
+            if let Err(e) = self.intern(value) {
+                panic!("failed to intern value: {}", e);
+            }
+        }
+    }
+}
+
+impl<'a, T: ?Sized, I, S> FromIterator<&'a T> for Pool<T, I, S>
+    where T: 'a + ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId,
+          S: BuildHasher + Default
+{
+    /// Create a pool containing each value produced by an iterator.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pool overflows.
+    fn from_iter<It: IntoIterator<Item=&'a T>>(iter: It) -> Self {
+        let mut pool = Pool::default();
+        pool.extend(iter);
+        pool
+    }
+}
+
+// ----------------------------------------------------------------
+// Serialization
+#[cfg(feature = "serde")]
+impl<I> serde::Serialize for Sym<I>
+    where I: SymbolId + serde::Serialize
+{
+    fn serialize<Z: serde::Serializer>(&self, serializer: Z) -> ::std::result::Result<Z::Ok, Z::Error> {
+        crate::sym::serialize(self, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, I> serde::Deserialize<'de> for Sym<I>
+    where I: SymbolId + serde::Deserialize<'de>
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> ::std::result::Result<Self, D::Error> {
+        crate::sym::deserialize(deserializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: ?Sized, I, S> Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    /// Call `f`, binding any symbols it deserializes on the current thread to
+    /// this pool, and return its result.
+    ///
+    /// Symbols are serialized as their raw IDs, so they can only be
+    /// deserialized for a pool &mdash; usually one that was itself just
+    /// deserialized.  Deserializing a symbol outside of any
+    /// `deserialize_symbols` call fails.  Calls can be nested, and the
+    /// innermost pool is used.
+    ///
+    /// ```rust
+    /// use symtern::prelude::*;
+    /// use symtern::{Pool, Sym};
+    ///
+    /// let mut pool = Pool::<str, u32>::new();
+    /// let sym = pool.intern("foo").expect("failed to intern a value");
+    /// let saved_pool = serde_json::to_string(&pool).unwrap();
+    /// let saved_sym = serde_json::to_string(&sym).unwrap();
+    ///
+    /// let loaded: Pool<str, u32> = serde_json::from_str(&saved_pool).unwrap();
+    /// let sym: Sym<u32> = loaded.deserialize_symbols(|| serde_json::from_str(&saved_sym)).unwrap();
+    /// assert_eq!(Ok("foo"), loaded.resolve(sym));
+    /// ```
+    pub fn deserialize_symbols<F, R>(&self, f: F) -> R
+        where F: FnOnce() -> R
+    {
+        crate::sym::deserialize_symbols(crate::sym::deserialization_id(self), f)
+    }
+}
+
+/// Pools are serialized as the sequence of their values in order of symbol ID.
+/// The hasher is not serialized; a deserialized pool uses a default-constructed
+/// instance of `S`.
+#[cfg(feature = "serde")]
+impl<T: ?Sized, I, S> serde::Serialize for Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + serde::Serialize,
+          I: SymbolId
+{
+    fn serialize<Z: serde::Serializer>(&self, serializer: Z) -> ::std::result::Result<Z::Ok, Z::Error> {
+        serializer.collect_seq(&self.lookup_vec)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: ?Sized, I, S> serde::Deserialize<'de> for Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T> + serde::Deserialize<'de>,
+          I: SymbolId,
+          S: BuildHasher + Default
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> ::std::result::Result<Self, D::Error> {
+        use std::marker::PhantomData;
+        use serde::de::{self, SeqAccess, Visitor};
+
+        struct PoolVisitor<T: ?Sized, I, S>(PhantomData<(Box<T>, I, S)>);
+
+        impl<'de, T: ?Sized, I, S> Visitor<'de> for PoolVisitor<T, I, S>
+            where T: ToOwned + Eq + Hash,
+                  T::Owned: Eq + Hash + Borrow<T> + serde::Deserialize<'de>,
+                  I: SymbolId,
+                  S: BuildHasher + Default
+        {
+            type Value = Pool<T, I, S>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a sequence of distinct interned values")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> ::std::result::Result<Self::Value, A::Error> {
+                let mut pool = Pool::with_capacity_and_hasher(seq.size_hint().unwrap_or(0), S::default());
+                while let Some(value) = seq.next_element::<T::Owned>()? {
+                    match pool.push_distinct(value) {
+                        Ok(Some(_)) => (),
+                        Ok(None) => return Err(de::Error::custom("duplicate value in serialized pool")),
+                        Err(e) => return Err(de::Error::custom(e)),
+                    }
+                }
+                Ok(pool)
+            }
+        }
+
+        deserializer.deserialize_seq(PoolVisitor(PhantomData))
+    }
+}
+
+
 #[cfg(test)]
 mod tests {
+    use std::hash::{BuildHasherDefault, Hash, Hasher};
+
     use super::Pool;
     use crate::traits::*;
     use crate::ErrorKind;
 
+    /// Value type with a deliberately terrible hash function: every instance
+    /// hashes to the same value.
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    struct Colliding(u32);
+
+    impl Hash for Colliding {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            0u8.hash(state);
+        }
+    }
+
+    /// Check that distinct values with identical hashes are given distinct
+    /// symbols, and that each resolves to its own value.
+    #[test]
+    fn colliding_values_get_distinct_symbols() {
+        let mut pool = Pool::<Colliding,u8>::new();
+
+        let syms: Vec<_> = (0..10).map(|i| pool.intern(&Colliding(i)).expect("failed to intern value"))
+            .collect();
+        assert_eq!(10, pool.len());
+
+        for (i, &sym) in syms.iter().enumerate() {
+            assert_eq!(Ok(&Colliding(i as u32)), pool.resolve(sym));
+        }
+        for i in 1..syms.len() {
+            assert!(syms[i - 1] != syms[i]);
+        }
+    }
+
+    /// Check that re-interning a value that collides with others still
+    /// returns that value's original symbol.
+    #[test]
+    fn colliding_values_are_deduplicated() {
+        let mut pool = Pool::<Colliding,u8>::new();
+
+        let a = pool.intern(&Colliding(1)).expect("failed to intern value");
+        let b = pool.intern(&Colliding(2)).expect("failed to intern value");
+        let c = pool.intern(&Colliding(3)).expect("failed to intern value");
+
+        assert_eq!(Ok(b), pool.intern(&Colliding(2)));
+        assert_eq!(Ok(a), pool.intern(&Colliding(1)));
+        assert_eq!(Ok(c), pool.intern(&Colliding(3)));
+        assert_eq!(3, pool.len());
+    }
+
+    /// Hasher that ignores its input entirely.
+    #[derive(Default)]
+    struct ConstantHasher;
+
+    impl Hasher for ConstantHasher {
+        fn finish(&self) -> u64 { 0 }
+        fn write(&mut self, _: &[u8]) {}
+    }
+
+    /// Check that a pool configured with a custom hasher uses it, and still
+    /// works correctly when that hasher maps every value to the same hash.
+    #[test]
+    fn works_with_custom_hasher() {
+        let mut pool = Pool::<str, u16, BuildHasherDefault<ConstantHasher>>::with_hasher(Default::default());
+
+        let foo = pool.intern("foo").expect("failed to intern value");
+        let bar = pool.intern("bar").expect("failed to intern value");
+        assert!(foo != bar);
+        assert_eq!(Ok(foo), pool.intern("foo"));
+        assert_eq!(Ok("foo"), pool.resolve(foo));
+        assert_eq!(Ok("bar"), pool.resolve(bar));
+    }
+
+    /// Check that stats count stored bytes, and report every value after the
+    /// first as a collision when all values hash alike.
+    #[test]
+    fn stats_count_data_and_collisions() {
+        let mut pool = Pool::<str, u16>::new();
+        assert_eq!(0.0, pool.stats().load_factor);
+        pool.intern("foo").expect("failed to intern value");
+        pool.intern("quux").expect("failed to intern value");
+        let stats = pool.stats();
+        assert_eq!((2, 7, 0), (stats.len, stats.data_bytes, stats.collisions));
+        assert!(stats.load_factor > 0.0 && stats.load_factor <= 1.0);
+        assert!(stats.overhead_bytes > 0);
+
+        // Reserving space grows the reported overhead.
+        pool.reserve(1000);
+        assert!(pool.stats().overhead_bytes > stats.overhead_bytes);
+
+        let mut pool = Pool::<str, u16, BuildHasherDefault<ConstantHasher>>::with_hasher(Default::default());
+        for value in &["foo", "bar", "baz"] {
+            pool.intern(value).expect("failed to intern value");
+        }
+        assert_eq!(2, pool.stats().collisions);
+    }
+
+    /// Check that a pool whose ID type is wider than `usize` works.
+    #[test]
+    fn works_with_u128_ids() {
+        let mut pool = Pool::<str, u128>::new();
+        assert!(! pool.is_full());
+        let foo = pool.intern("foo").expect("failed to intern value");
+        assert_eq!(Ok("foo"), pool.resolve(foo));
+        assert!(! pool.is_full());
+    }
+
+    /// Check that pools work with non-zero IDs, and that their symbols leave
+    /// room for `Option`'s discriminant.
+    #[test]
+    fn nonzero_ids_fill_niche() {
+        use std::mem::size_of;
+        use std::num::{NonZeroU16, NonZeroU32};
+        use super::Sym;
+
+        let mut pool = Pool::<str, NonZeroU32>::new();
+        let foo = pool.intern("foo").expect("failed to intern value");
+        let bar = pool.intern("bar").expect("failed to intern value");
+        assert_eq!((1, 2), (foo.id.get(), bar.id.get()));
+        assert_eq!((Some(0), Some(1)), (foo.id.to_usize(), bar.id.to_usize()));
+        assert_eq!(Ok("foo"), pool.resolve(foo));
+        assert_eq!(Ok("bar"), pool.resolve(bar));
+
+        assert_eq!(size_of::<Sym<NonZeroU32>>(), size_of::<Option<Sym<NonZeroU32>>>());
+        #[cfg(not(debug_assertions))]
+        {
+            assert_eq!(4, size_of::<Option<Sym<NonZeroU32>>>());
+            assert_eq!(2, size_of::<Option<Sym<NonZeroU16>>>());
+        }
+        // Debug builds' symbols also carry their pool's ID.
+        #[cfg(debug_assertions)]
+        {
+            use crate::sym::PoolId;
+            assert_eq!(size_of::<(u32, PoolId)>(), size_of::<Option<Sym<NonZeroU32>>>());
+            assert_eq!(size_of::<(u16, PoolId)>(), size_of::<Option<Sym<NonZeroU16>>>());
+        }
+
+        // Zero is unavailable, so the pool holds one value fewer than a
+        // `u16`-indexed one would.
+        let mut pool = Pool::<u32, NonZeroU16>::new();
+        for i in 0..65535 {
+            pool.intern(&i).expect("failed to intern value");
+        }
+        assert!(pool.is_full());
+        assert_eq!(ErrorKind::PoolOverflow, pool.intern(&65535).unwrap_err().kind());
+    }
+
+    /// Check that looking up a value does not intern it.
+    #[test]
+    fn lookup_does_not_intern() {
+        let mut pool = Pool::<str,u16>::new();
+        assert_eq!(None, pool.get("foo"));
+        assert!(pool.is_empty());
+
+        let foo = pool.intern("foo").expect("failed to intern value");
+        assert_eq!(Some(foo), pool.get("foo"));
+        assert_eq!(None, pool.get("bar"));
+        assert_eq!(1, pool.len());
+        assert!(pool.contains_symbol(foo));
+
+        // Symbols from another pool are not contained, and don't cause a panic.
+        let mut other = Pool::<str,u16>::new();
+        other.intern("bar").expect("failed to intern value");
+        #[cfg(debug_assertions)]
+        assert!(! other.contains_symbol(foo));
+        assert!(! Pool::<str,u16>::new().contains_symbol(foo));
+    }
+
+    /// Check that lookup distinguishes values with colliding hashes.
+    #[test]
+    fn lookup_handles_colliding_values() {
+        let mut pool = Pool::<Colliding,u8>::new();
+        let a = pool.intern(&Colliding(1)).expect("failed to intern value");
+        let b = pool.intern(&Colliding(2)).expect("failed to intern value");
+
+        assert_eq!(Some(a), pool.get(&Colliding(1)));
+        assert_eq!(Some(b), pool.get(&Colliding(2)));
+        assert_eq!(None, pool.get(&Colliding(3)));
+    }
+
+    /// Check that iteration visits every entry in order of symbol ID.
+    #[test]
+    fn iterates_in_id_order() {
+        let mut pool = Pool::<str,u16>::new();
+        let syms: Vec<_> = ["foo", "bar", "baz"].iter()
+            .map(|s| pool.intern(s).expect("failed to intern value"))
+            .collect();
+
+        assert_eq!(vec![(syms[0], "foo"), (syms[1], "bar"), (syms[2], "baz")],
+                   pool.iter().collect::<Vec<_>>());
+        assert_eq!(syms, pool.symbols().collect::<Vec<_>>());
+        assert_eq!(vec!["foo", "bar", "baz"], pool.values().collect::<Vec<_>>());
+        assert_eq!(3, (&pool).into_iter().len());
+        assert_eq!("bar", &pool[syms[1]]);
+    }
+
+    /// Check that a pool can be built from, and extended by, an iterator.
+    #[test]
+    fn can_collect_and_extend() {
+        let mut pool: Pool<str,u16> = vec!["a", "b", "a", "c"].into_iter().collect();
+        assert_eq!(3, pool.len());
+
+        pool.extend(vec!["c", "d"]);
+        assert_eq!(vec!["a", "b", "c", "d"], pool.values().collect::<Vec<_>>());
+    }
+
+    /// Check that capacity can be reserved and released.
+    #[test]
+    fn capacity_can_be_managed() {
+        let mut pool = Pool::<str,u32>::with_capacity(100);
+        assert!(pool.capacity() >= 100);
+        assert!(pool.is_empty());
+
+        pool.reserve(1000);
+        assert!(pool.capacity() >= 1000);
+        pool.try_reserve(2000).expect("failed to reserve space");
+        assert!(pool.capacity() >= 2000);
+
+        let sym = pool.intern("foo").expect("failed to intern value");
+        pool.shrink_to_fit();
+        assert!(pool.capacity() < 2000);
+        assert_eq!(Ok("foo"), pool.resolve(sym));
+    }
+
+    /// Check that clearing a pool empties it without releasing its storage.
+    #[test]
+    fn clear_keeps_capacity() {
+        let mut pool = Pool::<str,u32>::with_capacity(100);
+        for i in 0..50 {
+            pool.intern(&i.to_string()).expect("failed to intern value");
+        }
+        let capacity = pool.capacity();
+        pool.clear();
+        assert!(pool.is_empty());
+        assert_eq!(None, pool.get("7"));
+        assert_eq!(capacity, pool.capacity());
+
+        let sym = pool.intern("foo").expect("failed to intern value");
+        assert_eq!(Ok("foo"), pool.resolve(sym));
+        assert_eq!(1, pool.len());
+    }
+
+    /// Check that merging deduplicates values and remaps the other pool's
+    /// symbols.
+    #[test]
+    fn merge_remaps_symbols() {
+        let mut a = Pool::<str,u16>::new();
+        let a_foo = a.intern("foo").expect("failed to intern value");
+        a.intern("bar").expect("failed to intern value");
+
+        let mut b = Pool::<str,u16>::new();
+        let mut syms: Vec<_> = ["baz", "foo", "quux"].iter().map(|v| b.intern(v).expect("failed to intern value")).collect();
+
+        let remap = a.merge(b).expect("failed to merge pools");
+        assert_eq!(3, remap.len());
+        assert_eq!(4, a.len());
+        assert_eq!(Some(a_foo), remap.get(syms[1]));
+
+        remap.apply(&mut syms).expect("failed to remap symbols");
+        let values: Vec<_> = syms.iter().map(|&s| a.resolve(s).expect("failed to resolve value")).collect();
+        assert_eq!(vec!["baz", "foo", "quux"], values);
+        assert_eq!(vec![("foo", 0), ("bar", 1), ("baz", 2), ("quux", 3)],
+                   a.iter().map(|(s, v)| (v, s.id)).collect::<Vec<_>>());
+    }
+
+    /// Check that a merge that would overflow the pool changes nothing.
+    #[test]
+    fn merge_overflow_leaves_pool_unchanged() {
+        let mut a = Pool::<u16,u8>::new();
+        for i in 0..200u16 {
+            a.intern(&i).expect("failed to intern value");
+        }
+        let mut b = Pool::<u16,u8>::new();
+        for i in 150..257u16 {
+            b.intern(&i).expect("failed to intern value");
+        }
+
+        assert_eq!(ErrorKind::PoolOverflow, a.merge(b.clone()).unwrap_err().kind());
+        assert_eq!(200, a.len());
+        assert_eq!(None, a.get(&200));
+
+        b = Pool::new();
+        for i in 150..256u16 {
+            b.intern(&i).expect("failed to intern value");
+        }
+        a.merge(b).expect("failed to merge pools");
+        assert!(a.is_full());
+    }
+
+    /// Check that `retain` compacts the pool and remaps surviving symbols.
+    #[test]
+    fn retain_compacts_and_remaps() {
+        let mut pool = Pool::<str,u16>::new();
+        let mut syms: Vec<_> = (0..10).map(|i| pool.intern(&i.to_string()).expect("failed to intern value")).collect();
+
+        let remap = pool.retain(|_, value| value.parse::<u32>().unwrap() % 3 == 0);
+        assert_eq!(4, pool.len());
+        assert_eq!(None, pool.get("4"));
+        assert_eq!(None, remap.get(syms[4]));
+        assert_eq!(ErrorKind::NoSuchSymbol, remap.apply(&mut syms).unwrap_err().kind());
+
+        let mut kept: Vec<_> = syms.iter().cloned().step_by(3).collect();
+        remap.apply(&mut kept).expect("failed to remap symbols");
+        assert_eq!(vec![0, 1, 2, 3], kept.iter().map(|s| s.id).collect::<Vec<_>>());
+        assert_eq!(Some(kept[2]), pool.get("6"));
+        assert_eq!(Ok("9"), pool.resolve(kept[3]));
+
+        // The index was rebuilt, so new values get the next dense ID.
+        assert_eq!(4, pool.intern("10").expect("failed to intern value").id);
+    }
+
+    /// Check that symbols can be compared and sorted by value, including
+    /// after new values are added.
+    #[test]
+    fn compares_and_sorts_by_value() {
+        use std::cmp::Ordering;
+
+        let mut pool = Pool::<str,u16>::new();
+        let mut syms: Vec<_> = ["delta", "alpha", "charlie"].iter().map(|v| pool.intern(v).expect("failed to intern value")).collect();
+        assert_eq!(Ordering::Less, pool.cmp_values(syms[1], syms[0]));
+        assert_eq!(Ordering::Equal, pool.cmp_values(syms[2], syms[2]));
+
+        // Ranks must be recomputed once a new value is added.
+        syms.push(pool.intern("bravo").expect("failed to intern value"));
+        pool.sort_by_value(&mut syms);
+        let values: Vec<_> = syms.iter().map(|&s| pool.resolve(s).expect("failed to resolve value")).collect();
+        assert_eq!(vec!["alpha", "bravo", "charlie", "delta"], values);
+    }
+
+    /// Check that sorting a pool renumbers its values in value order.
+    #[test]
+    fn sort_values_orders_ids_by_value() {
+        let mut pool = Pool::<str,u16>::new();
+        let mut syms: Vec<_> = ["delta", "alpha", "charlie", "bravo"].iter().map(|v| pool.intern(v).expect("failed to intern value")).collect();
+
+        let remap = pool.sort_values();
+        remap.apply(&mut syms).expect("failed to remap symbols");
+        assert_eq!(vec![3, 0, 2, 1], syms.iter().map(|s| s.id).collect::<Vec<_>>());
+        assert_eq!(Some(syms[0]), pool.get("delta"));
+        assert!(syms[1] < syms[3] && syms[3] < syms[2] && syms[2] < syms[0]);
+
+        let (frozen, remap) = pool.freeze_sorted();
+        remap.apply(&mut syms).expect("failed to remap symbols");
+        assert_eq!(Ok("bravo"), frozen.resolve(syms[3]));
+    }
+
+    /// Check that an unsatisfiable reservation is reported as an error.
+    #[test]
+    fn try_reserve_reports_allocation_failure() {
+        let mut pool = Pool::<str,u32>::new();
+        match pool.try_reserve(usize::max_value()) {
+            Ok(_) => panic!("unexpected `Ok` when reserving an impossible amount of space"),
+            Err(e) => assert_eq!(ErrorKind::AllocationFailed, e.kind()),
+        }
+    }
+
+    /// Check that overflow is still detected when the new value's hash
+    /// collides with that of an existing value.
+    #[test]
+    fn colliding_value_in_full_pool_overflows() {
+        let mut pool = Pool::<Colliding,u8>::new();
+        for i in 0..256 {
+            pool.intern(&Colliding(i)).expect("failed to intern value");
+        }
+        assert!(pool.is_full());
+
+        pool.intern(&Colliding(42)).expect("failed to intern previously-interned value");
+        match pool.intern(&Colliding(256)) {
+            Ok(_) => panic!("unexpected `Ok` when interning unseen value in full pool"),
+            Err(e) => assert_eq!(ErrorKind::PoolOverflow, e.kind()),
+        }
+    }
+
     #[test]
     fn resolve_returns_expected_results() {
         let mut p1 = Pool::<str,u16>::new();
@@ -288,4 +1456,93 @@ mod tests {
             Err(e) => assert_eq!(ErrorKind::PoolOverflow, e.kind()),
         }
     }
+
+    #[test]
+    fn snapshot_round_trip_keeps_symbol_ids() {
+        let mut pool = Pool::<str, u16>::new();
+        let values = ["", "foo", "bar", "a rather longer string with some ünïcödé in it"];
+        for value in &values {
+            pool.intern(value).expect("failed to intern value");
+        }
+
+        let mut snapshot = Vec::new();
+        pool.write_snapshot(&mut snapshot).expect("failed to write snapshot");
+        let loaded = Pool::<str, u16>::read_snapshot(&snapshot[..]).expect("failed to read snapshot");
+        assert_eq!(values.to_vec(), loaded.values().collect::<Vec<_>>());
+
+        let mut bytes = Pool::<[u8], u16>::new();
+        for value in &values {
+            bytes.intern(value.as_bytes()).expect("failed to intern value");
+        }
+        let mut byte_snapshot = Vec::new();
+        bytes.write_snapshot(&mut byte_snapshot).expect("failed to write snapshot");
+        assert_eq!(snapshot, byte_snapshot);
+        let loaded = Pool::<[u8], u16>::read_snapshot(&byte_snapshot[..]).expect("failed to read snapshot");
+        assert_eq!(bytes.values().collect::<Vec<_>>(), loaded.values().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn snapshot_loading_rejects_invalid_snapshots() {
+        let mut pool = Pool::<[u8], u16>::new();
+        pool.intern(&b"foo"[..]).expect("failed to intern value");
+        pool.intern(&[0xff][..]).expect("failed to intern value");
+        let mut snapshot = Vec::new();
+        pool.write_snapshot(&mut snapshot).expect("failed to write snapshot");
+
+        let kind = |bytes: &[u8]| Pool::<str, u16>::read_snapshot(bytes).unwrap_err().kind();
+        assert_eq!(ErrorKind::IdWidthMismatch, Pool::<[u8], u32>::read_snapshot(&snapshot[..]).unwrap_err().kind());
+        assert_eq!(ErrorKind::TruncatedSnapshot, Pool::<[u8], u16>::read_snapshot(&snapshot[..snapshot.len() - 1]).unwrap_err().kind());
+        // Not valid UTF-8.
+        assert_eq!(ErrorKind::CorruptSnapshot, kind(&snapshot[..]));
+
+        // A snapshot with a duplicate value cannot have come from a pool.
+        let mut dup = Vec::new();
+        crate::snapshot::write::<u16, _, _>(&mut dup, 2, vec![&b"foo"[..], &b"foo"[..]]).unwrap();
+        assert_eq!(ErrorKind::CorruptSnapshot, kind(&dup[..]));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip_keeps_symbol_ids() {
+        extern crate serde_json;
+
+        let mut pool = Pool::<str, u16>::new();
+        let syms: Vec<_> = ["foo", "bar", "baz"].iter()
+            .map(|s| pool.intern(s).expect("failed to intern value"))
+            .collect();
+
+        let pool_json = serde_json::to_string(&pool).expect("failed to serialize pool");
+        let syms_json = serde_json::to_string(&syms).expect("failed to serialize symbols");
+        assert_eq!((r#"["foo","bar","baz"]"#, "[0,1,2]"), (&pool_json[..], &syms_json[..]));
+
+        let mut pool2: Pool<str, u16> = serde_json::from_str(&pool_json).expect("failed to deserialize pool");
+        let syms2: Vec<super::Sym<u16>> = pool2.deserialize_symbols(|| serde_json::from_str(&syms_json))
+            .expect("failed to deserialize symbols");
+        assert_eq!(pool.len(), pool2.len());
+        for (sym, sym2) in syms.iter().zip(syms2.iter()) {
+            #[cfg(debug_assertions)]
+            assert_eq!(pool2.pool_id, sym2.pool_id);
+            assert_eq!(pool.resolve(*sym), pool2.resolve(*sym2));
+            assert_eq!(Ok(sym2.id), pool2.intern(&pool[*sym]).map(|s| s.id));
+        }
+
+        // Symbols cannot be deserialized without a pool to bind them to.
+        assert!(serde_json::from_str::<Vec<super::Sym<u16>>>(&syms_json).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialization_rejects_invalid_pools() {
+        extern crate serde_json;
+
+        assert!(serde_json::from_str::<Pool<str, u16>>(r#"["foo","bar","foo"]"#).is_err());
+
+        let values: Vec<u16> = (0..256).collect();
+        let json = serde_json::to_string(&values).unwrap();
+        assert_eq!(256, serde_json::from_str::<Pool<u16, u8>>(&json).expect("failed to deserialize full pool").len());
+
+        let values: Vec<u16> = (0..257).collect();
+        let json = serde_json::to_string(&values).unwrap();
+        assert!(serde_json::from_str::<Pool<u16, u8>>(&json).is_err());
+    }
 }
@@ -0,0 +1,458 @@
+// Copyright (C) 2016-2017 Symtern Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-Apache
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or
+// distributed except according to those terms.
+//! Thread-safe interner that can intern through a shared reference.
+// [Module documentation lives on the exported pool type, `ConcurrentPool`.]
+use std::borrow::{Borrow, ToOwned};
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+use std::sync::{OnceLock, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::{array, fmt, ptr, slice};
+
+use crate::traits::{Intern, Lookup, Resolve, Len, SymbolId};
+use crate::{core, Result, ErrorKind};
+use crate::core::{locate, Bucket, DefaultBuildHasher, HashMap, FIRST_CHUNK_LEN};
+use crate::sym::{Symbol as ISymbol, Pool as IPool};
+use crate::basic::Sym;
+
+/// Number of bits of each value's hash used to select the shard that
+/// indexes it.
+const SHARD_BITS: u32 = 4;
+
+/// Number of independently-locked shards in each pool's index.
+const SHARD_COUNT: usize = 1 << SHARD_BITS;
+
+/// Maximum number of storage chunks needed to hold `usize::MAX` values.
+const CHUNK_COUNT: usize = (usize::BITS - FIRST_CHUNK_LEN.trailing_zeros()) as usize;
+
+type Shard<I> = RwLock<HashMap<u64, Bucket<I>>>;
+
+/// Thread-safe interner that allows interning through a shared reference.
+///
+/// `ConcurrentPool` splits its index into a number of shards, each protected
+/// by its own lock, so that threads interning different values rarely
+/// contend with each other.  Stored values live in a list of fixed-capacity,
+/// append-only chunks: resolving a symbol never takes a lock, and the
+/// returned references remain valid for as long as the pool itself.
+///
+/// `ConcurrentPool` is `Sync` whenever its value and hasher types allow, so
+/// it can be shared between threads by reference (or by `Arc`):
+///
+/// ```rust
+/// use std::thread;
+/// use symtern::prelude::*;
+/// use symtern::ConcurrentPool;
+///
+/// let pool = ConcurrentPool::<str, u32>::new();
+/// let (a, b) = thread::scope(|scope| {
+///     let a = scope.spawn(|| pool.intern("shared").expect("failed to intern a value"));
+///     let b = scope.spawn(|| pool.intern("shared").expect("failed to intern a value"));
+///     (a.join().unwrap(), b.join().unwrap())
+/// });
+///
+/// assert_eq!(a, b);
+/// assert_eq!(Ok("shared"), pool.resolve(a));
+/// ```
+pub struct Pool<T: ?Sized, I = usize, S = DefaultBuildHasher>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    shards: Box<[Shard<I>]>,
+    chunks: [AtomicPtr<OnceLock<T::Owned>>; CHUNK_COUNT],
+    next_id: AtomicUsize,
+    hash_builder: S,
+    #[cfg(debug_assertions)]
+    pool_id: usize,
+    // Tells the compiler that we own the values pointed to by `chunks`, so
+    // that it can determine whether the pool is `Send` or `Sync`.
+    marker: PhantomData<Box<[OnceLock<T::Owned>]>>,
+}
+
+// (inherent impl)
+impl<T: ?Sized, I> Pool<T, I>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    /// Create a new, empty `ConcurrentPool` instance.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<T: ?Sized, I, S> Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId,
+          S: BuildHasher
+{
+    /// Create a new, empty `ConcurrentPool` instance that will use the given
+    /// hasher factory to hash interned values.
+    #[cfg(not(debug_assertions))]
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Pool{shards: (0..SHARD_COUNT).map(|_| Default::default()).collect(),
+             chunks: array::from_fn(|_| AtomicPtr::new(ptr::null_mut())),
+             next_id: AtomicUsize::new(0),
+             hash_builder,
+             marker: PhantomData}
+    }
+
+    /// Create a new, empty `ConcurrentPool` instance that will use the given
+    /// hasher factory to hash interned values.
+    #[cfg(debug_assertions)]
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Pool{shards: (0..SHARD_COUNT).map(|_| Default::default()).collect(),
+             chunks: array::from_fn(|_| AtomicPtr::new(ptr::null_mut())),
+             next_id: AtomicUsize::new(0),
+             hash_builder,
+             pool_id: crate::sym::next_pool_id(),
+             marker: PhantomData}
+    }
+
+    /// Fetch a reference to the pool's hasher factory.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+}
+
+impl<T: ?Sized, I, S> Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    /// Fetch the shard responsible for indexing values with the given hash.
+    fn shard(&self, key: u64) -> &Shard<I> {
+        &self.shards[(key >> (64 - SHARD_BITS)) as usize]
+    }
+
+    /// Fetch the storage chunk with the given index, if it has been allocated.
+    fn chunk(&self, idx: usize) -> Option<&[OnceLock<T::Owned>]> {
+        let ptr = self.chunks[idx].load(Ordering::Acquire);
+        if ptr.is_null() {
+            None
+        } else {
+            // Non-null chunk pointers always point to a live allocation of
+            // exactly this length, which is freed only when the pool is
+            // dropped.
+            Some(unsafe { slice::from_raw_parts(ptr, FIRST_CHUNK_LEN << idx) })
+        }
+    }
+
+    /// Fetch the storage chunk with the given index, allocating it if
+    /// necessary.
+    fn chunk_or_alloc(&self, idx: usize) -> &[OnceLock<T::Owned>] {
+        if let Some(chunk) = self.chunk(idx) {
+            return chunk;
+        }
+
+        let len = FIRST_CHUNK_LEN << idx;
+        let new_chunk: Box<[OnceLock<T::Owned>]> = (0..len).map(|_| OnceLock::new()).collect();
+        let new_ptr = Box::into_raw(new_chunk) as *mut OnceLock<T::Owned>;
+
+        // Another thread may have allocated the chunk while we were doing the
+        // same; if so, we throw ours away and use theirs.
+        match self.chunks[idx].compare_exchange(ptr::null_mut(), new_ptr, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => unsafe { slice::from_raw_parts(new_ptr, len) },
+            Err(existing) => unsafe {
+                drop(Box::from_raw(ptr::slice_from_raw_parts_mut(new_ptr, len)));
+                slice::from_raw_parts(existing, len)
+            },
+        }
+    }
+
+    /// Fetch the value stored at the given index, if any.
+    fn get_value(&self, idx: usize) -> Option<&T>
+        where T::Owned: Borrow<T>
+    {
+        let (chunk, offset) = locate(idx);
+        self.chunk(chunk).and_then(|c| c[offset].get()).map(Borrow::borrow)
+    }
+
+    /// Find the ID of a value equal to `value` in the given (locked) index.
+    fn find_id(&self, ids_map: &HashMap<u64, Bucket<I>>, key: u64, value: &T) -> Option<I>
+        where T::Owned: Borrow<T>
+    {
+        ids_map.get(&key).and_then(|bucket| {
+            bucket.ids().iter().cloned().find(|id| {
+                let idx = id.to_usize().expect("Unexpected failure to convert symbol ID to usize");
+                self.get_value(idx) == Some(value)
+            })
+        })
+    }
+
+    /// Reserve an ID for a new value, or return `None` if the pool is full.
+    fn allocate_id(&self) -> Option<usize> {
+        let max = I::max_value().to_usize().unwrap_or(usize::MAX);
+        self.next_id.fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| if n > max { None } else { n.checked_add(1) })
+            .ok()
+    }
+}
+
+/// Lock a shard for reading.  A shard's index is never left in an
+/// inconsistent state, so we ignore lock poisoning.
+fn read<I>(shard: &Shard<I>) -> RwLockReadGuard<'_, HashMap<u64, Bucket<I>>> {
+    shard.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Lock a shard for writing.  A shard's index is never left in an
+/// inconsistent state, so we ignore lock poisoning.
+fn write<I>(shard: &Shard<I>) -> RwLockWriteGuard<'_, HashMap<u64, Bucket<I>>> {
+    shard.write().unwrap_or_else(|e| e.into_inner())
+}
+
+impl<T: ?Sized, I, S> Drop for Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    fn drop(&mut self) {
+        for (idx, chunk) in self.chunks.iter_mut().enumerate() {
+            let ptr = *chunk.get_mut();
+            if ! ptr.is_null() {
+                unsafe { drop(Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, FIRST_CHUNK_LEN << idx))) };
+            }
+        }
+    }
+}
+
+impl<T: ?Sized, I, S> fmt::Debug for Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ConcurrentPool")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl<T: ?Sized, I, S> Len for Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    /// Get the number of entries contained in the pool.  This includes any
+    /// values currently being interned by other threads.
+    fn len(&self) -> usize {
+        self.next_id.load(Ordering::Acquire)
+    }
+
+    /// Check if the pool is "empty", i.e. has zero stored values.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Check if the number of interned symbols has reached the maximum allowed
+    /// for the pool's ID type.
+    fn is_full(&self) -> bool {
+        // Symbol IDs range from 0 to M, where M is given by `I::max_value()`;
+        // hence a pool containing N entries is full iff N == M + 1.
+        self.len() > I::max_value().to_usize().unwrap_or(usize::MAX)
+    }
+}
+
+impl<T: ?Sized, I, S> crate::sym::Pool for Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    type Symbol = Sym<I>;
+
+    #[cfg(debug_assertions)]
+    fn id(&self) -> crate::sym::PoolId {
+        self.pool_id
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn create_symbol(&self, id: <Self::Symbol as crate::sym::Symbol>::Id) -> Self::Symbol {
+        Sym::create(id)
+    }
+
+    #[cfg(debug_assertions)]
+    fn create_symbol(&self, id: <Self::Symbol as crate::sym::Symbol>::Id) -> Self::Symbol {
+        Sym::create(id, self.id())
+    }
+}
+
+// Default
+impl<T: ?Sized, I, S> Default for Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId,
+          S: BuildHasher + Default
+{
+    fn default() -> Self {
+        Pool::with_hasher(Default::default())
+    }
+}
+
+// Intern
+impl<T: ?Sized, I, S> Intern for &Pool<T, I, S>
+    where I: SymbolId,
+          T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          S: BuildHasher,
+{
+    type Input = T;
+    type Symbol = Sym<I>;
+
+    fn intern(self, value: &Self::Input) -> Result<Self::Symbol> {
+        let key = core::hash(&self.hash_builder, value);
+        let shard = self.shard(key);
+
+        // Most calls to `intern` are for values that already exist, so we
+        // first check for the value while holding only a read lock.
+        if let Some(id) = self.find_id(&read(shard), key, value) {
+            return Ok(self.create_symbol(id));
+        }
+
+        let mut ids_map = write(shard);
+        // Another thread may have interned the value since we checked.
+        if let Some(id) = self.find_id(&ids_map, key, value) {
+            return Ok(self.create_symbol(id));
+        }
+
+        let owned = value.to_owned();
+        let idx = match self.allocate_id() {
+            Some(idx) => idx,
+            None => return Err(ErrorKind::PoolOverflow.into()),
+        };
+        let (chunk, offset) = locate(idx);
+        if self.chunk_or_alloc(chunk)[offset].set(owned).is_err() {
+            unreachable!("Symbol ID was allocated twice");
+        }
+
+        // We do not expect this conversion to fail, since `allocate_id`
+        // checks that the new ID is representable.
+        let id = I::from_usize(idx)
+            .expect("Unexpected failure to convert symbol ID from usize");
+        core::insert_id(&mut ids_map, key, id);
+
+        Ok(self.create_symbol(id))
+    }
+}
+
+// ----------------------------------------------------------------
+// Lookup
+impl<T: ?Sized, I, S> Lookup for &Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId,
+          S: BuildHasher,
+{
+    type Input = T;
+    type Symbol = Sym<I>;
+
+    fn get(self, value: &Self::Input) -> Option<Self::Symbol> {
+        let key = core::hash(&self.hash_builder, value);
+        self.find_id(&read(self.shard(key)), key, value).map(|id| self.create_symbol(id))
+    }
+
+    fn contains_symbol(self, s: Self::Symbol) -> bool {
+        if is_foreign_symbol!(self, s) { return false; }
+        let idx = s.id().to_usize().expect("Unexpected failure to convert symbol ID to usize");
+        self.get_value(idx).is_some()
+    }
+}
+
+// ----------------------------------------------------------------
+// Resolve
+impl<'a, T: ?Sized, I, S> Resolve for &'a Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId
+{
+    type Input = Sym<I>;
+    type Output = &'a T;
+
+    fn resolve(self, s: Self::Input) -> Result<Self::Output> {
+        check_matching_pool!(self, s);
+        // We previously converted the ID _from_ a usize, so this conversion should _not_ fail.
+        let idx = s.id().to_usize().expect("Unexpected failure to convert symbol ID to usize");
+        self.get_value(idx).ok_or_else(|| ErrorKind::NoSuchSymbol.into())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::Pool;
+    use crate::traits::*;
+
+    /// Check that the pool can be shared between threads.
+    #[test]
+    fn is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Pool<str, u32>>();
+    }
+
+    #[test]
+    fn resolve_returns_expected_results() {
+        let pool = Pool::<str, u16>::new();
+        let foo = pool.intern("foo").expect("failed to intern value");
+        let foo_ref = pool.resolve(foo).expect("failed to resolve value");
+        let bar = pool.intern("bar").expect("failed to intern value");
+
+        assert!(foo != bar);
+        assert_eq!(Ok(foo), pool.intern("foo"));
+        assert_eq!("foo", foo_ref);
+        assert_eq!(Ok("bar"), pool.resolve(bar));
+        assert_eq!(Some(bar), pool.get("bar"));
+        assert_eq!(None, pool.get("baz"));
+        assert!(pool.contains_symbol(bar));
+    }
+
+    /// Check that threads interning overlapping sets of values agree on the
+    /// symbol for each value.
+    #[test]
+    fn threads_agree_on_symbols() {
+        let pool = Pool::<str, u32>::new();
+        let strings: Vec<String> = (0..2000).map(|i| format!("value {}", i)).collect();
+
+        let results: Vec<Vec<_>> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..8).map(|t| {
+                let pool = &pool;
+                let strings = &strings;
+                scope.spawn(move || {
+                    // Each thread visits the strings in a different order.
+                    let mut syms = vec![None; strings.len()];
+                    for i in 0..strings.len() {
+                        let idx = (i * 7 + t * 251) % strings.len();
+                        syms[idx] = Some(pool.intern(&strings[idx]).expect("failed to intern value"));
+                    }
+                    syms.into_iter().map(Option::unwrap).collect()
+                })
+            }).collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        assert_eq!(strings.len(), pool.len());
+        for syms in &results[1..] {
+            assert_eq!(&results[0], syms);
+        }
+        for (s, &sym) in strings.iter().zip(results[0].iter()) {
+            assert_eq!(Ok(&s[..]), pool.resolve(sym));
+        }
+    }
+
+    #[test]
+    fn has_expected_capacity() {
+        let pool = Pool::<u16, u8>::new();
+        for i in 0u16..256 {
+            pool.intern(&i).expect("failed to intern value");
+        }
+        assert!(pool.is_full());
+        assert!(pool.intern(&123).is_ok());
+        assert!(pool.intern(&256).is_err());
+        assert_eq!(256, pool.len());
+    }
+}
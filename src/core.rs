@@ -5,17 +5,103 @@
 // license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
 // at your option. This file may not be copied, modified, or
 // distributed except according to those terms.
-use std::hash::{Hash, Hasher};
+use std::collections::hash_map::Entry;
+use std::hash::{BuildHasher, BuildHasherDefault, Hash};
+use std::slice;
+
+use crate::traits::SymbolId;
 
 #[cfg(feature = "fnv")]
 pub type DefaultHashAlgo = ::fnv::FnvHasher;
 #[cfg(not(feature = "fnv"))]
 pub type DefaultHashAlgo = ::std::collections::hash_map::DefaultHasher;
 
+/// Hasher factory used by [`Pool`](struct.Pool.html) when no other is
+/// specified.  This produces FNV hashers when the `fnv` feature is enabled
+/// (the default), and unkeyed SipHash hashers otherwise.
+pub type DefaultBuildHasher = BuildHasherDefault<DefaultHashAlgo>;
+
+
+/// Hash an object using a hasher created by the given factory.
+pub fn hash<T: ?Sized + Hash, S: BuildHasher>(hash_builder: &S, obj: &T) -> u64 {
+    hash_builder.hash_one(obj)
+}
+
+
+/// Map type used to index symbol IDs by the hashes of their values.
+#[cfg(feature = "fnv")]
+pub type HashMap<K, V> = ::fnv::FnvHashMap<K, V>;
+
+/// Map type used to index symbol IDs by the hashes of their values.
+#[cfg(not(feature = "fnv"))]
+pub type HashMap<K, V> = ::std::collections::HashMap<K, V>;
+
+/// Set of symbol IDs whose values share a single hash.
+///
+/// Nearly every bucket will hold exactly one ID, so we avoid allocating
+/// a vector until a second (colliding) value shows up.
+#[derive(Clone, Debug)]
+pub enum Bucket<I> {
+    One(I),
+    Many(Vec<I>),
+}
+
+impl<I: SymbolId> Bucket<I> {
+    /// Fetch the IDs stored in the bucket.
+    pub fn ids(&self) -> &[I] {
+        match *self {
+            Bucket::One(ref id) => slice::from_ref(id),
+            Bucket::Many(ref ids) => &ids[..],
+        }
+    }
+
+    /// Add an ID to the bucket.
+    pub fn push(&mut self, id: I) {
+        match *self {
+            Bucket::One(first) => *self = Bucket::Many(vec![first, id]),
+            Bucket::Many(ref mut ids) => ids.push(id),
+        }
+    }
+}
+
+/// Record `id` in the bucket for hash `key`, creating the bucket if needed.
+pub fn insert_id<I: SymbolId>(ids_map: &mut HashMap<u64, Bucket<I>>, key: u64, id: I) {
+    match ids_map.entry(key) {
+        Entry::Occupied(mut e) => e.get_mut().push(id),
+        Entry::Vacant(e) => { e.insert(Bucket::One(id)); },
+    }
+}
+
+/// Remove `id` from the bucket for hash `key`, discarding the bucket if it
+/// becomes empty.
+pub fn remove_id<I: SymbolId>(ids_map: &mut HashMap<u64, Bucket<I>>, key: u64, id: I) {
+    if let Entry::Occupied(mut e) = ids_map.entry(key) {
+        match *e.get_mut() {
+            Bucket::One(other) => if other == id {
+                e.remove();
+            },
+            Bucket::Many(ref mut ids) => {
+                ids.retain(|&other| other != id);
+                if let [last] = ids[..] {
+                    *e.get_mut() = Bucket::One(last);
+                }
+            },
+        }
+    }
+}
+
+/// Number of values held by the first chunk of storage in pools that store
+/// values in a list of fixed-capacity chunks.  Each subsequent chunk is twice
+/// the size of the one before it.
+pub const FIRST_CHUNK_LEN: usize = 16;
 
-/// Hash an object using the given hasher type.
-pub fn hash<T: ?Sized + Hash, H: Hasher + Default>(obj: &T) -> u64 {
-    let mut hasher = H::default();
-    obj.hash(&mut hasher);
-    hasher.finish()
+/// Find the chunk index, and offset within that chunk, at which the value with
+/// the given index is stored in a list of chunks sized according to
+/// `FIRST_CHUNK_LEN`.
+pub fn locate(idx: usize) -> (usize, usize) {
+    // Chunk `k` holds `FIRST_CHUNK_LEN << k` values, and starts at index
+    // `FIRST_CHUNK_LEN * (2^k - 1)`.
+    let n = idx / FIRST_CHUNK_LEN + 1;
+    let chunk = (usize::BITS - 1 - n.leading_zeros()) as usize;
+    (chunk, idx - FIRST_CHUNK_LEN * ((1 << chunk) - 1))
 }
@@ -41,6 +41,15 @@ impl ::std::error::Error for Error {
         match self.kind {
             ErrorKind::PoolOverflow => "out of space for new symbols",
             ErrorKind::NoSuchSymbol => "no such symbol found",
+            ErrorKind::StaleSymbol => "symbol refers to a value that has been removed",
+            ErrorKind::AllocationFailed => "memory allocation failed",
+            ErrorKind::ValueTooLarge => "value is too large for the pool to store",
+            ErrorKind::PoolBorrowed => "pool is borrowed by a resolved value",
+            ErrorKind::IdWidthMismatch => "snapshot was written with a different symbol-ID type",
+            ErrorKind::UnsupportedSnapshotVersion => "snapshot uses an unsupported format version",
+            ErrorKind::TruncatedSnapshot => "snapshot ended unexpectedly",
+            ErrorKind::CorruptSnapshot => "snapshot is corrupt",
+            ErrorKind::SnapshotReadFailed => "failed to read snapshot",
             ErrorKind::__DoNotMatchThisVariant(_) => unreachable!(),
         }
     }
@@ -64,6 +73,37 @@ pub enum ErrorKind {
     /// resolve it.
     NoSuchSymbol,
 
+    /// The value represented by the given symbol has been removed from the
+    /// pool.
+    StaleSymbol,
+
+    /// The interner was unable to allocate memory for its storage.
+    AllocationFailed,
+
+    /// The value is larger than the pool can store.
+    ValueTooLarge,
+
+    /// The pool cannot add values while a value it resolved is still
+    /// borrowed.
+    PoolBorrowed,
+
+    /// A snapshot was written by a pool whose symbol-ID type has a different
+    /// size than that of the pool trying to load it.
+    IdWidthMismatch,
+
+    /// A snapshot was written using a format version this version of the
+    /// crate does not understand.
+    UnsupportedSnapshotVersion,
+
+    /// A snapshot ended before all of its contents could be read.
+    TruncatedSnapshot,
+
+    /// A snapshot's contents are invalid or do not match its checksum.
+    CorruptSnapshot,
+
+    /// An I/O error occurred while reading a snapshot.
+    SnapshotReadFailed,
+
     /// This enum is subject to change as additional interner implementations
     /// are added, so you should use an ident/wildcard to catch any variants
     /// you do not explicitly handle.
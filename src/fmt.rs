@@ -0,0 +1,242 @@
+// Copyright (C) 2016-2017 Symtern Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-Apache
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or
+// distributed except according to those terms.
+//! Formatting symbols together with the values they represent.
+//!
+//! Symbols are only IDs, so on their own their `Debug` output says nothing
+//! about the values they stand for.  There are two ways to include those
+//! values.
+//!
+//! For a single symbol, [`Resolve::display`] returns a [`Display`] wrapper
+//! that formats the symbol's value:
+//!
+//! ```rust
+//! use symtern::prelude::*;
+//! use symtern::Pool;
+//!
+//! let mut pool = Pool::<str, u32>::new();
+//! let sym = pool.intern("foo").expect("failed to intern a value");
+//! assert_eq!("foo", format!("{}", pool.display(sym)));
+//! ```
+//!
+//! For larger structures containing many symbols, like syntax trees,
+//! [`with_resolver`] makes a pool available to the `Debug` implementations
+//! of `Sym`, `InlineSym` and `LumaSym` for the duration of a closure:
+//!
+//! ```rust
+//! use symtern::prelude::*;
+//! use symtern::Pool;
+//!
+//! let mut pool = Pool::<str, u32>::new();
+//! let sym = pool.intern("foo").expect("failed to intern a value");
+//!
+//! let output = symtern::fmt::with_resolver(&pool, || format!("{:?}", sym));
+//! assert_eq!("Sym(0 = \"foo\")", output);
+//! ```
+//!
+//! Scopes can be nested, and the innermost resolver that recognizes a symbol
+//! is used.  Symbols that no resolver recognizes are formatted as usual.
+//! Pools can only tell their own symbols from other pools' symbols in debug
+//! builds, though: in release builds, a resolver recognizes any symbol of its
+//! symbol type whose ID is in range, so when nesting scopes for pools of the
+//! same type, the innermost one may format another pool's symbols with its
+//! own values.
+//! Inlined symbols created by the [`Inline`] adaptor never need a resolver,
+//! since they hold their own text, which the adaptor's encoder decodes (see
+//! [`DebugPack`]).
+//!
+//! [`Resolve::display`]: ../traits/trait.Resolve.html#method.display
+//! [`Display`]: struct.Display.html
+//! [`with_resolver`]: fn.with_resolver.html
+//! [`Inline`]: ../adaptors/struct.Inline.html
+//! [`DebugPack`]: ../adaptors/trait.DebugPack.html
+use std::any::Any;
+use std::cell::RefCell;
+use std::fmt::{self, Debug, Formatter};
+use std::mem;
+
+use crate::traits::Resolve;
+
+/// Wrapper that formats the value represented by a symbol.  Returned by
+/// [`Resolve::display`](../traits/trait.Resolve.html#method.display).
+///
+/// If the symbol cannot be resolved, the resolution error is formatted in
+/// angle brackets instead.
+pub struct Display<R: Resolve> {
+    resolver: R,
+    symbol: R::Input,
+}
+
+impl<R: Resolve> Display<R> {
+    pub(crate) fn new(resolver: R, symbol: R::Input) -> Self {
+        Display{resolver, symbol}
+    }
+}
+
+impl<R> fmt::Display for Display<R>
+    where R: Resolve + Copy,
+          R::Input: Clone,
+          R::Output: fmt::Display
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.resolver.resolve(self.symbol.clone()) {
+            Ok(value) => value.fmt(f),
+            Err(e) => write!(f, "<{}>", e),
+        }
+    }
+}
+
+impl<R> Debug for Display<R>
+    where R: Resolve + Copy,
+          R::Input: Clone,
+          R::Output: Debug
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.resolver.resolve(self.symbol.clone()) {
+            Ok(value) => value.fmt(f),
+            Err(e) => write!(f, "<{}>", e),
+        }
+    }
+}
+
+/// Interface for pools that can provide values to symbols' `Debug`
+/// implementations through [`with_resolver`](fn.with_resolver.html).
+pub trait DebugResolver {
+    /// If `symbol` is a symbol created by this pool, call `f` with a `Debug`
+    /// view of its value and return the result; otherwise return `None`.
+    ///
+    /// Implementations should pass adaptors' symbols on to the wrapped
+    /// pool's implementation, and must not panic on symbols from other
+    /// pools.
+    fn with_debug_value(&self, symbol: &dyn Any, f: &mut dyn FnMut(&dyn Debug) -> fmt::Result) -> Option<fmt::Result>;
+}
+
+thread_local! {
+    /// Resolvers made available by active `with_resolver` calls, innermost
+    /// last.  The pointers' lifetimes are erased; each is removed before the
+    /// `with_resolver` call that added it returns.
+    static RESOLVERS: RefCell<Vec<*const (dyn DebugResolver + 'static)>> = RefCell::new(Vec::new());
+}
+
+/// Removes the innermost resolver when a `with_resolver` scope ends, even if
+/// it ends by panicking.
+struct ScopeGuard;
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        RESOLVERS.with(|resolvers| resolvers.borrow_mut().pop());
+    }
+}
+
+/// Call `f` with `resolver` available to the `Debug` implementations of
+/// symbol types on the current thread, and return its result.
+///
+/// See the [module documentation](index.html) for an example.
+pub fn with_resolver<R, F, T>(resolver: &R, f: F) -> T
+    where R: DebugResolver,
+          F: FnOnce() -> T
+{
+    let resolver: *const (dyn DebugResolver + '_) = resolver;
+    // The guard removes the pointer before `resolver`'s borrow ends.
+    let resolver: *const (dyn DebugResolver + 'static) = unsafe { mem::transmute(resolver) };
+    RESOLVERS.with(|resolvers| resolvers.borrow_mut().push(resolver));
+    let _guard = ScopeGuard;
+    f()
+}
+
+/// Format a symbol as `name(id = value)` using the innermost resolver that
+/// recognizes it.  Returns `None` if there is no such resolver.
+pub(crate) fn fmt_symbol(symbol: &dyn Any, name: &str, id: &dyn Debug, f: &mut Formatter) -> Option<fmt::Result> {
+    let mut write = |value: &dyn Debug| write!(f, "{}({:?} = {:?})", name, id, value);
+    RESOLVERS.try_with(|resolvers| {
+        let resolvers = resolvers.try_borrow().ok()?;
+        resolvers.iter().rev()
+            .find_map(|&resolver| unsafe { &*resolver }.with_debug_value(symbol, &mut write))
+    }).ok().and_then(|result| result)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::basic::Pool;
+    use crate::adaptors::{Inline, Luma};
+    use super::with_resolver;
+
+    /// Check that each resolver formats only its own symbols, and that
+    /// nested scopes are unwound.
+    #[test]
+    fn formats_symbols_in_scope() {
+        let mut a = Pool::<str, u32>::new();
+        let mut b = Pool::<str, u16>::new();
+        let x = a.intern("x").expect("failed to intern value");
+        let y = b.intern("y").expect("failed to intern value");
+
+        let plain = format!("{:?}", x);
+        assert!(plain.starts_with("Sym { id: 0"));
+
+        with_resolver(&a, || {
+            assert_eq!("Sym(0 = \"x\")", format!("{:?}", x));
+            assert!(format!("{:?}", y).starts_with("Sym { id: 0"));
+            with_resolver(&b, || {
+                assert_eq!("Sym(0 = \"x\")", format!("{:?}", x));
+                assert_eq!("Sym(0 = \"y\")", format!("{:?}", y));
+            });
+            assert!(format!("{:?}", y).starts_with("Sym { id: 0"));
+        });
+        assert_eq!(plain, format!("{:?}", x));
+    }
+
+    /// Check which pool formats a symbol when nested resolvers have the same
+    /// type, and so recognize the same symbol type.
+    #[test]
+    fn formats_symbols_of_same_typed_pools() {
+        let mut a = Pool::<str, u32>::new();
+        let mut b = Pool::<str, u32>::new();
+        let x = a.intern("x").expect("failed to intern value");
+        let y = b.intern("y").expect("failed to intern value");
+
+        with_resolver(&a, || {
+            with_resolver(&b, || {
+                assert_eq!("Sym(0 = \"y\")", format!("{:?}", y));
+                // Only debug builds can tell that `x` belongs to the outer pool.
+                #[cfg(debug_assertions)]
+                assert_eq!("Sym(0 = \"x\")", format!("{:?}", x));
+                #[cfg(not(debug_assertions))]
+                assert_eq!("Sym(0 = \"y\")", format!("{:?}", x));
+            });
+        });
+    }
+
+    /// Check that adaptors' symbols are formatted through the wrapped pool.
+    #[test]
+    fn formats_adaptor_symbols() {
+        let mut inline = Inline::<Pool<str, u64>>::new();
+        let short = inline.intern("abc").expect("failed to intern value");
+        let long = inline.intern("a longer value").expect("failed to intern value");
+        assert_eq!("InlineSym(\"abc\")", format!("{:?}", short));
+        with_resolver(&inline, || {
+            assert_eq!("InlineSym(0 = \"a longer value\")", format!("{:?}", long));
+        });
+
+        let luma = Luma::from(Pool::<str, u32>::new());
+        let sym = luma.intern("foo").expect("failed to intern value");
+        with_resolver(&luma, || {
+            assert_eq!("LumaSym(0 = \"foo\")", format!("{:?}", sym));
+        });
+    }
+
+    /// Check that `display` formats values, and errors for unresolvable
+    /// symbols.
+    #[test]
+    fn display_formats_value() {
+        let mut pool = Pool::<str, u32>::new();
+        let sym = pool.intern("foo").expect("failed to intern value");
+        assert_eq!("foo", pool.display(sym).to_string());
+        assert_eq!("\"foo\"", format!("{:?}", pool.display(sym)));
+    }
+}
@@ -0,0 +1,435 @@
+// Copyright (C) 2016-2017 Symtern Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-Apache
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or
+// distributed except according to those terms.
+//! Immutable interner that looks up values using a minimal perfect hash.
+// [Module documentation lives on the exported pool type, `FrozenPool`.]
+use std::borrow::{Borrow, ToOwned};
+use std::any::Any;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+
+use crate::traits::{Lookup, Resolve, ResolveUnchecked, Iterate, Len, SymbolId};
+use crate::{core, basic, Result};
+use crate::core::DefaultBuildHasher;
+use crate::sym::Pool as IPool;
+use crate::basic::Sym;
+use crate::fmt::DebugResolver;
+
+/// Flag set on a displacement value that holds a slot index directly.
+const DIRECT: u32 = 1 << 31;
+
+/// Number of displacement values to try for each bucket before starting over
+/// with a new seed.
+const MAX_DISPLACEMENT: u32 = 1 << 20;
+
+/// Immutable interner that looks up values using a minimal perfect hash.
+///
+/// A `FrozenPool` is created by calling [`freeze`] on a [`Pool`] that will not
+/// change again.  It holds the same values, with the same symbol IDs, as the
+/// pool it came from &mdash; so symbols obtained from the original pool
+/// resolve on the frozen one &mdash; but replaces the pool's hash map with
+/// a [minimal perfect hash function][mphf] built over its contents.  Looking
+/// up a value then costs a single hash, two array reads and one comparison,
+/// and the index occupies only four bytes plus the size of one symbol ID per
+/// value.
+///
+/// Since it cannot be modified, a `FrozenPool` is `Send` and `Sync` whenever
+/// its values and hasher are, and can be shared between threads with `Arc`:
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use std::thread;
+/// use symtern::prelude::*;
+/// use symtern::Pool;
+///
+/// let mut pool = Pool::<str, u32>::new();
+/// let vec = pool.intern("Vec").expect("failed to intern a value");
+///
+/// let frozen = Arc::new(pool.freeze());
+/// let shared = Arc::clone(&frozen);
+/// thread::spawn(move || {
+///     assert_eq!(Ok("Vec"), shared.resolve(vec));
+///     assert_eq!(Some(vec), shared.get("Vec"));
+/// }).join().unwrap();
+///
+/// // `thaw` turns the frozen pool back into a mutable one.
+/// let mut pool = Arc::try_unwrap(frozen).unwrap().thaw();
+/// assert!(pool.intern("String").is_ok());
+/// ```
+///
+/// [`freeze`]: struct.Pool.html#method.freeze
+/// [`Pool`]: struct.Pool.html
+/// [mphf]: https://en.wikipedia.org/wiki/Perfect_hash_function#Minimal_perfect_hash_function
+pub struct Pool<T: ?Sized, I = usize, S = DefaultBuildHasher>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    /// Pool holding the values, hasher and pool ID.  Its own index is empty.
+    pool: basic::Pool<T, I, S>,
+    /// Seed mixed into every hash, chosen while building the hash function.
+    seed: u64,
+    /// Displacement value for each bucket, which picks the slot of every
+    /// value in the bucket.
+    displacements: Box<[u32]>,
+    /// ID of the value placed in each slot.
+    slots: Box<[I]>,
+    /// Hash and ID of each value whose hash is identical to that of
+    /// a value with a lower ID, sorted by hash.  These values can't be told
+    /// apart by any function of their hash, so we can't give them slots.
+    overflow: Box<[(u64, I)]>,
+}
+
+/// Scramble the bits of a 64-bit value (the SplitMix64 finalizer).
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Find the bucket for a value with the given hash.
+fn bucket(key: u64, seed: u64, n_buckets: usize) -> usize {
+    (mix(key ^ seed) % n_buckets as u64) as usize
+}
+
+/// Find the slot for a value with the given hash, in a bucket with the given
+/// displacement.
+fn slot(key: u64, seed: u64, displacement: u32, n_slots: usize) -> usize {
+    let d = (u64::from(displacement) + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    (mix(key.wrapping_add(seed) ^ d) % n_slots as u64) as usize
+}
+
+/// Displacement for each bucket, and the index of the key placed in each slot.
+type Placement = (Box<[u32]>, Box<[usize]>);
+
+/// Try to build a minimal perfect hash function over the given (distinct)
+/// hashes using the given seed.
+fn place(keys: &[u64], seed: u64) -> Option<Placement> {
+    let n = keys.len();
+    let buckets: Vec<usize> = keys.iter().map(|&key| bucket(key, seed, n)).collect();
+    let mut sizes = vec![0usize; n];
+    for &b in &buckets {
+        sizes[b] += 1;
+    }
+
+    // Place the largest buckets first, while there is the most room.
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by_key(|&k| (usize::MAX - sizes[buckets[k]], buckets[k]));
+
+    let mut displacements = vec![0u32; n].into_boxed_slice();
+    let mut slots = vec![usize::MAX; n].into_boxed_slice();
+    let mut next_free = 0;
+    let mut chosen = Vec::new();
+    for group in order.chunk_by(|&a, &b| buckets[a] == buckets[b]) {
+        let b = buckets[group[0]];
+        if group.len() == 1 && n < DIRECT as usize {
+            while slots[next_free] != usize::MAX {
+                next_free += 1;
+            }
+            slots[next_free] = group[0];
+            displacements[b] = DIRECT | next_free as u32;
+            continue;
+        }
+
+        let mut found = false;
+        for d in 0..MAX_DISPLACEMENT {
+            chosen.clear();
+            for &k in group {
+                let s = slot(keys[k], seed, d, n);
+                if slots[s] != usize::MAX || chosen.contains(&s) {
+                    break;
+                }
+                chosen.push(s);
+            }
+            if chosen.len() == group.len() {
+                for (&k, &s) in group.iter().zip(chosen.iter()) {
+                    slots[s] = k;
+                }
+                displacements[b] = d;
+                found = true;
+                break;
+            }
+        }
+        if ! found {
+            return None;
+        }
+    }
+    Some((displacements, slots))
+}
+
+impl<T: ?Sized, I, S> Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId,
+          S: BuildHasher
+{
+    /// Build the perfect hash function for a pool whose index has been
+    /// discarded.
+    pub(crate) fn new(pool: basic::Pool<T, I, S>) -> Self {
+        let hashes: Vec<u64> = pool.values().map(|value| core::hash(pool.hasher(), value)).collect();
+
+        // Sorting is stable, so the first of several values with the same hash
+        // gets a slot and the rest go in the overflow list.
+        let mut order: Vec<usize> = (0..hashes.len()).collect();
+        order.sort_by_key(|&idx| hashes[idx]);
+        let to_id = |idx: usize| I::from_usize(idx).expect("Unexpected failure to convert symbol ID from usize");
+        let mut ids = Vec::with_capacity(order.len());
+        let mut keys = Vec::with_capacity(order.len());
+        let mut overflow = Vec::new();
+        for idx in order {
+            if keys.last() == Some(&hashes[idx]) {
+                overflow.push((hashes[idx], to_id(idx)));
+            } else {
+                keys.push(hashes[idx]);
+                ids.push(to_id(idx));
+            }
+        }
+
+        let (seed, displacements, slots) = (0u64..)
+            .map(mix)
+            .find_map(|seed| place(&keys, seed).map(|(d, s)| (seed, d, s)))
+            .expect("Unexpected failure to build a perfect hash function");
+
+        Pool{pool,
+             seed,
+             displacements,
+             slots: slots.iter().map(|&k| ids[k]).collect(),
+             overflow: overflow.into_boxed_slice()}
+    }
+
+    /// Convert the pool back into a mutable [`Pool`], which will contain the
+    /// same values with the same symbol IDs.
+    ///
+    /// [`Pool`]: struct.Pool.html
+    pub fn thaw(self) -> basic::Pool<T, I, S> {
+        let mut pool = self.pool;
+        pool.rebuild_index();
+        pool
+    }
+
+    /// Fetch a reference to the pool's hasher factory.
+    pub fn hasher(&self) -> &S {
+        self.pool.hasher()
+    }
+
+    /// Find the ID of a stored value equal to `value`.
+    fn find_id(&self, value: &T) -> Option<I> {
+        if self.slots.is_empty() {
+            return None;
+        }
+        let key = core::hash(self.pool.hasher(), value);
+        let d = self.displacements[bucket(key, self.seed, self.displacements.len())];
+        let s = if d & DIRECT != 0 {
+            (d & ! DIRECT) as usize
+        } else {
+            slot(key, self.seed, d, self.slots.len())
+        };
+
+        let matches = |id: &I| unsafe { self.pool.resolve_unchecked(self.pool.create_symbol(*id)) } == value;
+        let id = self.slots[s];
+        if matches(&id) {
+            return Some(id);
+        }
+        let start = self.overflow.partition_point(|&(k, _)| k < key);
+        self.overflow[start..].iter()
+            .take_while(|&&(k, _)| k == key)
+            .map(|&(_, id)| id)
+            .find(matches)
+    }
+}
+
+impl<T: ?Sized, I, S> fmt::Debug for Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FrozenPool")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl<T: ?Sized, I, S> DebugResolver for Pool<T, I, S>
+    where T: ToOwned + Eq + Hash + fmt::Debug,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId + 'static
+{
+    fn with_debug_value(&self, symbol: &dyn Any, f: &mut dyn FnMut(&dyn fmt::Debug) -> fmt::Result) -> Option<fmt::Result> {
+        self.pool.with_debug_value(symbol, f)
+    }
+}
+
+impl<T: ?Sized, I, S> Len for Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    /// Get the number of entries contained in the pool.
+    fn len(&self) -> usize {
+        self.pool.len()
+    }
+
+    /// Check if the pool is "empty", i.e. has zero stored values.
+    fn is_empty(&self) -> bool {
+        self.pool.is_empty()
+    }
+
+    /// Check if the number of interned symbols has reached the maximum allowed
+    /// for the pool's ID type.
+    fn is_full(&self) -> bool {
+        self.pool.is_full()
+    }
+}
+
+impl<T: ?Sized, I, S> crate::sym::Pool for Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    type Symbol = Sym<I>;
+
+    #[cfg(debug_assertions)]
+    fn id(&self) -> crate::sym::PoolId {
+        self.pool.id()
+    }
+
+    fn create_symbol(&self, id: <Self::Symbol as crate::sym::Symbol>::Id) -> Self::Symbol {
+        self.pool.create_symbol(id)
+    }
+}
+
+// ----------------------------------------------------------------
+// Lookup
+impl<T: ?Sized, I, S> Lookup for &Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId,
+          S: BuildHasher,
+{
+    type Input = T;
+    type Symbol = Sym<I>;
+
+    fn get(self, value: &Self::Input) -> Option<Self::Symbol> {
+        self.find_id(value).map(|id| self.create_symbol(id))
+    }
+
+    fn contains_symbol(self, s: Self::Symbol) -> bool {
+        self.pool.contains_symbol(s)
+    }
+}
+
+// ----------------------------------------------------------------
+// Resolve
+impl<'a, T: ?Sized, I, S> Resolve for &'a Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId
+{
+    type Input = Sym<I>;
+    type Output = &'a T;
+
+    fn resolve(self, s: Self::Input) -> Result<Self::Output> {
+        self.pool.resolve(s)
+    }
+}
+
+impl<T: ?Sized, I, S> ResolveUnchecked for &Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId
+{
+    unsafe fn resolve_unchecked(self, symbol: Self::Input) -> Self::Output {
+        self.pool.resolve_unchecked(symbol)
+    }
+}
+
+// ----------------------------------------------------------------
+// Iteration
+impl<'a, T: ?Sized, I, S> Iterate for &'a Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId
+{
+    type Symbol = Sym<I>;
+    type Output = &'a T;
+    type Iter = basic::Iter<'a, T, I, S>;
+
+    fn iter(self) -> Self::Iter {
+        self.pool.iter()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::hash::{Hash, Hasher};
+
+    use crate::basic::Pool;
+    use crate::traits::*;
+
+    #[test]
+    fn is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<super::Pool<str, u32>>();
+    }
+
+    #[test]
+    fn keeps_symbol_ids() {
+        let mut pool = Pool::<str, u32>::new();
+        let strings: Vec<String> = (0..5000).map(|i| format!("value {}", i)).collect();
+        let syms: Vec<_> = strings.iter().map(|s| pool.intern(s).expect("failed to intern value")).collect();
+
+        let frozen = pool.freeze();
+        assert_eq!(strings.len(), frozen.len());
+        for (s, &sym) in strings.iter().zip(syms.iter()) {
+            assert_eq!(Ok(&s[..]), frozen.resolve(sym));
+            assert_eq!(Some(sym), frozen.get(s));
+        }
+        assert_eq!(None, frozen.get("value 5000"));
+        assert_eq!(None, frozen.get(""));
+
+        let mut pool = frozen.thaw();
+        for (s, &sym) in strings.iter().zip(syms.iter()) {
+            assert_eq!(Ok(sym), pool.intern(s));
+        }
+    }
+
+    #[test]
+    fn handles_empty_and_tiny_pools() {
+        let frozen = Pool::<str, u8>::new().freeze();
+        assert!(frozen.is_empty());
+        assert_eq!(None, frozen.get("foo"));
+
+        let mut pool = Pool::<str, u8>::new();
+        let foo = pool.intern("foo").expect("failed to intern value");
+        let frozen = pool.freeze();
+        assert_eq!(Some(foo), frozen.get("foo"));
+        assert_eq!(None, frozen.get("bar"));
+    }
+
+    /// Values with identical hashes can't be placed by the perfect hash
+    /// function, but must still be found.
+    #[test]
+    fn finds_values_with_identical_hashes() {
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        struct Colliding(u16);
+        impl Hash for Colliding {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                (self.0 % 3).hash(state);
+            }
+        }
+
+        let mut pool = Pool::<Colliding, u16>::new();
+        let syms: Vec<_> = (0..100).map(|i| pool.intern(&Colliding(i)).expect("failed to intern value")).collect();
+        let frozen = pool.freeze();
+        for (i, &sym) in syms.iter().enumerate() {
+            assert_eq!(Some(sym), frozen.get(&Colliding(i as u16)));
+        }
+        assert_eq!(None, frozen.get(&Colliding(100)));
+    }
+}
@@ -44,10 +44,27 @@
 //! }
 //! ```
 //!
+//! For string-heavy workloads, [`ArenaPool`] packs interned strings into
+//! large shared chunks instead of allocating each one separately, and
+//! [`AppendPool`] can intern values through a shared reference while
+//! previously-resolved values remain borrowed.  [`ConcurrentPool`] does the
+//! same for pools shared between threads.  [`MappedPool`] resolves symbols
+//! directly out of the bytes of a saved snapshot, without copying any values.
+//! A `Pool` that will not change again can be turned into a [`FrozenPool`],
+//! which looks values up using a perfect hash.  Long-running programs that
+//! need to discard values can use [`RecyclingPool`], which reuses the IDs of
+//! removed values and detects symbols that refer to them.
+//!
 //! ### Adaptors
 //!
 //! For an overview of the available adaptors, see the [`adaptors` module].
 //!
+//! ### Formatting
+//!
+//! Symbols' `Debug` output shows only their IDs.  To see the values they
+//! represent, use [`Resolve::display`] or the scoped resolvers in the [`fmt`
+//! module].
+//!
 //! ## More examples
 //!
 //! [Symbol types](traits/trait.Symbol.html) are `Copy`:  they can be passed by
@@ -83,21 +100,57 @@
 //! all symbol instances to allow run-time detection of attempts to resolve
 //! a symbol on the wrong resolver, and any such attempt will trigger a panic.
 //!
+//! ## Serialization
+//!
+//! When the `serde` feature is enabled, [`Pool`] and the symbol types
+//! implement Serde's `Serialize` and `Deserialize` traits.  Symbols are
+//! serialized as their raw IDs, and a deserialized pool assigns each value the
+//! same ID it had in the original pool.  Symbols are deserialized for
+//! a particular pool, inside a call to its [`deserialize_symbols`] method, and
+//! then resolve to the same values they did before they were saved.
+//!
 //! [`Pool`]: struct.Pool.html
+//! [`deserialize_symbols`]: struct.Pool.html#method.deserialize_symbols
+//! [`ArenaPool`]: struct.ArenaPool.html
+//! [`AppendPool`]: struct.AppendPool.html
+//! [`ConcurrentPool`]: struct.ConcurrentPool.html
+//! [`MappedPool`]: struct.MappedPool.html
+//! [`FrozenPool`]: struct.FrozenPool.html
+//! [`RecyclingPool`]: struct.RecyclingPool.html
 //! [`adaptors` module]: adaptors/index.html
+//! [`Resolve::display`]: traits/trait.Resolve.html#method.display
+//! [`fmt` module]: fmt/index.html
 //! [`traits` module]: traits/index.html
 #![warn(missing_docs)]
 extern crate num_traits;
 #[cfg(feature = "fnv")] extern crate fnv;
+#[cfg(feature = "serde")] extern crate serde;
 
 #[macro_use] mod sym;
 mod core;
 mod error;
+mod snapshot;
 
 pub mod traits;
+pub mod fmt;
 mod basic;
+mod arena;
+mod append;
+mod concurrent;
+mod mapped;
+mod frozen;
+mod recycling;
+mod remap;
 pub mod adaptors;
 pub mod prelude;
 
 pub use crate::error::{Result, Error, ErrorKind};
-pub use crate::basic::{Pool, Sym};
+pub use crate::basic::{Pool, Sym, Iter};
+pub use crate::arena::{Pool as ArenaPool, Iter as ArenaIter};
+pub use crate::append::{Pool as AppendPool, Iter as AppendIter};
+pub use crate::concurrent::Pool as ConcurrentPool;
+pub use crate::mapped::Pool as MappedPool;
+pub use crate::frozen::Pool as FrozenPool;
+pub use crate::recycling::{Pool as RecyclingPool, Sym as RecyclingSym};
+pub use crate::remap::SymbolRemap;
+pub use crate::core::DefaultBuildHasher;
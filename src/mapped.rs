@@ -0,0 +1,313 @@
+// Copyright (C) 2016-2017 Symtern Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-Apache
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or
+// distributed except according to those terms.
+//! Read-only interner that resolves symbols directly from snapshot bytes.
+// [Module documentation lives on the exported pool type, `MappedPool`.]
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+use std::sync::OnceLock;
+
+use crate::traits::{Lookup, Resolve, Len, SymbolId};
+use crate::{core, snapshot, Result, ErrorKind};
+use crate::core::{Bucket, DefaultBuildHasher, HashMap};
+use crate::sym::{Symbol as ISymbol, Pool as IPool};
+use crate::basic::Sym;
+
+/// Read-only interner that resolves symbols directly out of the bytes of
+/// a snapshot.
+///
+/// `MappedPool` accepts any snapshot written by [`Pool::write_snapshot`] for
+/// a pool of `str` or `[u8]` values.  The snapshot is validated in full when
+/// the `MappedPool` is created, after which resolving a symbol returns
+/// a slice of the snapshot itself: values are never copied, and the only
+/// allocation is a single table holding the position of each value.  The
+/// index used by [`Lookup`] is built the first time it is needed, so processes
+/// that only resolve symbols never pay for it.
+///
+/// Each value has the same symbol ID it had in the pool that wrote the
+/// snapshot.
+///
+/// ```rust
+/// use symtern::prelude::*;
+/// use symtern::{Pool, MappedPool};
+///
+/// let mut pool = Pool::<str, u32>::new();
+/// pool.intern("Vec").expect("failed to intern a value");
+/// pool.intern("String").expect("failed to intern a value");
+///
+/// let mut snapshot = Vec::new();
+/// pool.write_snapshot(&mut snapshot).expect("failed to write snapshot");
+///
+/// let mapped = MappedPool::<str, u32>::new(&snapshot).expect("invalid snapshot");
+/// let string = mapped.get("String").expect("value missing from snapshot");
+/// assert_eq!(Ok("String"), mapped.resolve(string));
+/// assert_eq!(2, mapped.len());
+/// ```
+///
+/// To share a snapshot file between processes without reading it into
+/// memory, map it with a crate like `memmap2` and pass the mapped bytes to
+/// [`new`](#method.new):
+///
+/// ```rust,ignore
+/// let file = std::fs::File::open("symbols.snapshot")?;
+/// let map = unsafe { memmap2::Mmap::map(&file)? };
+/// let pool = MappedPool::<str, u32>::new(&map[..])?;
+/// ```
+///
+/// [`Pool::write_snapshot`]: struct.Pool.html#method.write_snapshot
+/// [`Lookup`]: traits/trait.Lookup.html
+pub struct Pool<'a, T: ?Sized, I = usize, S = DefaultBuildHasher>
+    where I: SymbolId
+{
+    bytes: &'a [u8],
+    /// Offset of each value's record in `bytes`, in order of symbol ID.
+    offsets: Box<[usize]>,
+    ids_map: OnceLock<HashMap<u64, Bucket<I>>>,
+    hash_builder: S,
+    #[cfg(debug_assertions)]
+    pool_id: usize,
+    marker: PhantomData<&'a T>,
+}
+
+// (inherent impl)
+impl<'a, T: ?Sized, I> Pool<'a, T, I>
+    where T: snapshot::Value,
+          I: SymbolId
+{
+    /// Validate a snapshot and create a `MappedPool` that resolves symbols
+    /// from it.
+    ///
+    /// Fails with `ErrorKind::IdWidthMismatch` if the snapshot was written by
+    /// a pool with a different symbol-ID size, `ErrorKind::TruncatedSnapshot`
+    /// if it is incomplete, and `ErrorKind::CorruptSnapshot` if its contents
+    /// are otherwise invalid.
+    pub fn new(bytes: &'a [u8]) -> Result<Self> {
+        Pool::with_hasher(bytes, Default::default())
+    }
+}
+
+impl<'a, T: ?Sized, I, S> Pool<'a, T, I, S>
+    where T: snapshot::Value,
+          I: SymbolId,
+          S: BuildHasher
+{
+    /// Validate a snapshot and create a `MappedPool` that resolves symbols
+    /// from it, and will use the given hasher factory to look up values.
+    #[cfg(not(debug_assertions))]
+    pub fn with_hasher(bytes: &'a [u8], hash_builder: S) -> Result<Self> {
+        Ok(Pool{bytes,
+                offsets: Self::validate(bytes)?,
+                ids_map: OnceLock::new(),
+                hash_builder,
+                marker: PhantomData})
+    }
+
+    /// Validate a snapshot and create a `MappedPool` that resolves symbols
+    /// from it, and will use the given hasher factory to look up values.
+    #[cfg(debug_assertions)]
+    pub fn with_hasher(bytes: &'a [u8], hash_builder: S) -> Result<Self> {
+        Ok(Pool{bytes,
+                offsets: Self::validate(bytes)?,
+                ids_map: OnceLock::new(),
+                hash_builder,
+                pool_id: crate::sym::next_pool_id(),
+                marker: PhantomData})
+    }
+
+    /// Fetch a reference to the pool's hasher factory.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Check that `bytes` holds a valid snapshot of `T` values, and
+    /// find the offset of each value's record.
+    fn validate(bytes: &[u8]) -> Result<Box<[usize]>> {
+        let mut offsets = Vec::new();
+        snapshot::parse::<I, _>(bytes, |offset, value| {
+            match T::from_bytes(value) {
+                Some(_) => {
+                    offsets.push(offset);
+                    Ok(())
+                },
+                None => Err(ErrorKind::CorruptSnapshot.into()),
+            }
+        })?;
+        Ok(offsets.into_boxed_slice())
+    }
+
+    /// Fetch the value with the given index.
+    fn get_value(&self, idx: usize) -> Option<&'a T> {
+        self.offsets.get(idx).map(|&offset| {
+            let (value, _) = snapshot::record_at(self.bytes, offset)
+                .expect("Unexpected failure to read a validated snapshot record");
+            // Every record was checked by `validate`.
+            unsafe { T::from_bytes_unchecked(value) }
+        })
+    }
+
+    /// Fetch the index used to look up values, building it if necessary.
+    fn ids_map(&self) -> &HashMap<u64, Bucket<I>>
+        where T: Hash
+    {
+        self.ids_map.get_or_init(|| {
+            let mut ids_map = HashMap::default();
+            ids_map.reserve(self.offsets.len());
+            for idx in 0..self.offsets.len() {
+                let value = self.get_value(idx).expect("Unexpected missing snapshot value");
+                let id = I::from_usize(idx).expect("Unexpected failure to convert symbol ID from usize");
+                core::insert_id(&mut ids_map, core::hash(&self.hash_builder, value), id);
+            }
+            ids_map
+        })
+    }
+}
+
+impl<'a, T: ?Sized, I, S> Len for Pool<'a, T, I, S>
+    where I: SymbolId
+{
+    /// Get the number of entries contained in the pool.
+    fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Check if the pool is "empty", i.e. has zero stored values.
+    fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// Check if the number of interned symbols has reached the maximum allowed
+    /// for the pool's ID type.
+    fn is_full(&self) -> bool {
+        // Symbol IDs range from 0 to M, where M is given by `I::max_value()`;
+        // hence a pool containing N entries is full iff N == M + 1.
+        self.len() > I::max_value().to_usize().unwrap_or(usize::MAX)
+    }
+}
+
+impl<'a, T: ?Sized, I, S> crate::sym::Pool for Pool<'a, T, I, S>
+    where I: SymbolId
+{
+    type Symbol = Sym<I>;
+
+    #[cfg(debug_assertions)]
+    fn id(&self) -> crate::sym::PoolId {
+        self.pool_id
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn create_symbol(&self, id: <Self::Symbol as crate::sym::Symbol>::Id) -> Self::Symbol {
+        Sym::create(id)
+    }
+
+    #[cfg(debug_assertions)]
+    fn create_symbol(&self, id: <Self::Symbol as crate::sym::Symbol>::Id) -> Self::Symbol {
+        Sym::create(id, self.id())
+    }
+}
+
+// ----------------------------------------------------------------
+// Lookup
+impl<'a, T: ?Sized, I, S> Lookup for &Pool<'a, T, I, S>
+    where T: snapshot::Value + Eq + Hash,
+          I: SymbolId,
+          S: BuildHasher,
+{
+    type Input = T;
+    type Symbol = Sym<I>;
+
+    fn get(self, value: &Self::Input) -> Option<Self::Symbol> {
+        let key = core::hash(&self.hash_builder, value);
+        self.ids_map().get(&key).and_then(|bucket| {
+            bucket.ids().iter().cloned().find(|id| {
+                let idx = id.to_usize().expect("Unexpected failure to convert symbol ID to usize");
+                self.get_value(idx) == Some(value)
+            })
+        }).map(|id| self.create_symbol(id))
+    }
+
+    fn contains_symbol(self, s: Self::Symbol) -> bool {
+        if is_foreign_symbol!(self, s) { return false; }
+        let idx = s.id().to_usize().expect("Unexpected failure to convert symbol ID to usize");
+        idx < self.offsets.len()
+    }
+}
+
+// ----------------------------------------------------------------
+// Resolve
+impl<'a, T: ?Sized, I, S> Resolve for &Pool<'a, T, I, S>
+    where T: snapshot::Value,
+          I: SymbolId,
+          S: BuildHasher,
+{
+    type Input = Sym<I>;
+    type Output = &'a T;
+
+    fn resolve(self, s: Self::Input) -> Result<Self::Output> {
+        check_matching_pool!(self, s);
+        // We previously converted the ID _from_ a usize, so this conversion should _not_ fail.
+        let idx = s.id().to_usize().expect("Unexpected failure to convert symbol ID to usize");
+        self.get_value(idx).ok_or_else(|| ErrorKind::NoSuchSymbol.into())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Pool as MappedPool;
+    use crate::basic::Pool;
+    use crate::traits::*;
+    use crate::ErrorKind;
+
+    fn snapshot_of(values: &[&str]) -> Vec<u8> {
+        let mut pool = Pool::<str, u16>::new();
+        for value in values {
+            pool.intern(value).expect("failed to intern value");
+        }
+        let mut snapshot = Vec::new();
+        pool.write_snapshot(&mut snapshot).expect("failed to write snapshot");
+        snapshot
+    }
+
+    #[test]
+    fn resolves_values_from_snapshot_bytes() {
+        let values = ["foo", "", "bar", "baz"];
+        let snapshot = snapshot_of(&values);
+        let mapped = MappedPool::<str, u16>::new(&snapshot).expect("failed to validate snapshot");
+        assert_eq!(values.len(), mapped.len());
+
+        for value in &values {
+            let sym = mapped.get(value).expect("failed to look up value");
+            let resolved = mapped.resolve(sym).expect("failed to resolve symbol");
+            assert_eq!(*value, resolved);
+
+            // Resolved values point into the snapshot itself.
+            assert!(snapshot.as_ptr_range().contains(&resolved.as_ptr()) || resolved.is_empty());
+        }
+        assert_eq!(None, mapped.get("quux"));
+
+        let bytes = MappedPool::<[u8], u16>::new(&snapshot).expect("failed to validate snapshot");
+        assert_eq!(Ok(&b"bar"[..]), bytes.get(b"bar").map(|s| bytes.resolve(s)).unwrap());
+    }
+
+    #[test]
+    fn rejects_invalid_snapshots() {
+        let snapshot = snapshot_of(&["foo", "bar"]);
+        let kind = |bytes: &[u8]| MappedPool::<str, u16>::new(bytes).map(|_| ()).unwrap_err().kind();
+
+        assert_eq!(ErrorKind::IdWidthMismatch, MappedPool::<str, u8>::new(&snapshot).map(|_| ()).unwrap_err().kind());
+        assert_eq!(ErrorKind::TruncatedSnapshot, kind(&snapshot[..10]));
+
+        let mut trailing = snapshot.clone();
+        trailing.push(0);
+        assert_eq!(ErrorKind::CorruptSnapshot, kind(&trailing));
+
+        let mut damaged = snapshot.clone();
+        let last = damaged.len() - 1;
+        damaged[last] ^= 1;
+        assert_eq!(ErrorKind::CorruptSnapshot, kind(&damaged));
+    }
+}
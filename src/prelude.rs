@@ -18,7 +18,13 @@
 // N.B. we're not using a brace-enclosed imports list here because it's harder
 // to read when rendered by rustdoc.
 pub use crate::traits::Len as SymternLen;
+pub use crate::traits::Capacity as SymternCapacity;
+pub use crate::traits::Clear as SymternClear;
 pub use crate::traits::Intern as SymternIntern;
+pub use crate::traits::Lookup as SymternLookup;
 pub use crate::traits::Resolve as SymternResolve;
 pub use crate::traits::ResolveUnchecked as SymternResolveUnchecked;
+pub use crate::traits::Iterate as SymternIterate;
+pub use crate::traits::Remove as SymternRemove;
+pub use crate::traits::Stats as SymternStats;
 
@@ -0,0 +1,375 @@
+// Copyright (C) 2016-2017 Symtern Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-Apache
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or
+// distributed except according to those terms.
+//! Interner that supports removing values and reuses their IDs.
+// [Module documentation lives on the exported pool type, `RecyclingPool`.]
+use std::borrow::{Borrow, ToOwned};
+use std::hash::{BuildHasher, Hash};
+
+use crate::traits::{Intern, Lookup, Remove, Resolve, Len, SymbolId};
+use crate::{core, Result, ErrorKind};
+use crate::core::{Bucket, DefaultBuildHasher, HashMap};
+use crate::sym::{Generation, Generational, Symbol as ISymbol};
+#[cfg(debug_assertions)]
+use crate::sym::Pool as IPool;
+
+make_sym! {
+    pub Sym<I> [generation]:
+    "Generation-checked symbol type used by [`RecyclingPool`](struct.RecyclingPool.html).";
+}
+
+/// Storage for a single symbol ID.
+#[derive(Clone, Debug)]
+struct Slot<O> {
+    /// Value currently stored under the ID, if any.
+    value: Option<O>,
+    /// Number of times the ID has been freed.
+    generation: Generation,
+}
+
+/// Hash-based interner that can remove values, and reuses the IDs of removed
+/// values for new ones.
+///
+/// Each ID freed by [`remove`] is put on a free list, from which IDs are taken
+/// for new values before any new ones are allocated &mdash; so the pool's
+/// memory use is proportional to the number of values it holds at any one
+/// time, rather than the number it has ever held.
+///
+/// To keep symbols for removed values from silently resolving to whichever
+/// value next takes their ID, every symbol carries a generation counter,
+/// which must match that of the ID's current value.  Resolving or removing
+/// a stale symbol fails with `ErrorKind::StaleSymbol`.  The counter for an ID
+/// wraps around after 2<sup>32</sup> removals, after which stale symbols can
+/// no longer be detected.
+///
+/// ```rust
+/// use symtern::prelude::*;
+/// use symtern::{RecyclingPool, ErrorKind};
+///
+/// let mut pool = RecyclingPool::<str, u8>::new();
+/// let tmp = pool.intern("temporary").expect("failed to intern a value");
+/// pool.remove(tmp).expect("failed to remove a value");
+/// assert_eq!(0, pool.len());
+///
+/// let kept = pool.intern("kept").expect("failed to intern a value");
+/// assert_eq!(Ok("kept"), pool.resolve(kept));
+/// assert_eq!(ErrorKind::StaleSymbol, pool.resolve(tmp).unwrap_err().kind());
+/// ```
+///
+/// [`remove`]: traits/trait.Remove.html#tymethod.remove
+#[derive(Debug)]
+pub struct Pool<T: ?Sized, I = usize, S = DefaultBuildHasher>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    ids_map: HashMap<u64, Bucket<I>>,
+    slots: Vec<Slot<T::Owned>>,
+    /// IDs of empty slots, most recently freed last.
+    free: Vec<I>,
+    /// Number of occupied slots.
+    len: usize,
+    hash_builder: S,
+    #[cfg(debug_assertions)]
+    pool_id: usize
+}
+
+// (inherent impl)
+impl<T: ?Sized, I> Pool<T, I>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    /// Create a new, empty `RecyclingPool` instance.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<T: ?Sized, I, S> Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId,
+          S: BuildHasher
+{
+    /// Create a new, empty `RecyclingPool` instance that will use the given
+    /// hasher factory to hash interned values.
+    #[cfg(not(debug_assertions))]
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Pool{ids_map: Default::default(),
+             slots: Vec::new(),
+             free: Vec::new(),
+             len: 0,
+             hash_builder}
+    }
+
+    /// Create a new, empty `RecyclingPool` instance that will use the given
+    /// hasher factory to hash interned values.
+    #[cfg(debug_assertions)]
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Pool{ids_map: Default::default(),
+             slots: Vec::new(),
+             free: Vec::new(),
+             len: 0,
+             hash_builder,
+             pool_id: crate::sym::next_pool_id()}
+    }
+
+    /// Fetch a reference to the pool's hasher factory.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Find the ID of a stored value equal to `value`, given the value's hash.
+    fn find_id(&self, key: u64, value: &T) -> Option<I>
+        where T::Owned: Borrow<T>
+    {
+        self.ids_map.get(&key).and_then(|bucket| {
+            bucket.ids().iter().cloned().find(|id| {
+                let idx = id.to_usize().expect("Unexpected failure to convert symbol ID to usize");
+                self.slots[idx].value.as_ref().map(Borrow::borrow) == Some(value)
+            })
+        })
+    }
+}
+
+impl<T: ?Sized, I, S> Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    /// Create a symbol for the current value of the given ID.
+    #[cfg(debug_assertions)]
+    fn symbol_for(&self, id: I) -> Sym<I> {
+        let idx = id.to_usize().expect("Unexpected failure to convert symbol ID to usize");
+        Sym::create_with_generation(id, self.slots[idx].generation, self.id())
+    }
+
+    /// Create a symbol for the current value of the given ID.
+    #[cfg(not(debug_assertions))]
+    fn symbol_for(&self, id: I) -> Sym<I> {
+        let idx = id.to_usize().expect("Unexpected failure to convert symbol ID to usize");
+        Sym::create_with_generation(id, self.slots[idx].generation)
+    }
+
+    /// Fetch the slot holding the given symbol's value, checking that the
+    /// value is still present.
+    fn slot(&self, s: Sym<I>) -> Result<&Slot<T::Owned>> {
+        let idx = s.id().to_usize().expect("Unexpected failure to convert symbol ID to usize");
+        match self.slots.get(idx) {
+            None => Err(ErrorKind::NoSuchSymbol.into()),
+            Some(slot) if slot.generation != s.generation() || slot.value.is_none() =>
+                Err(ErrorKind::StaleSymbol.into()),
+            Some(slot) => Ok(slot),
+        }
+    }
+}
+
+impl<T: ?Sized, I, S> Len for Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    /// Get the number of entries contained in the pool.  Removed values are
+    /// not counted.
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if the pool is "empty", i.e. has zero stored values.
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Check if the number of interned symbols has reached the maximum allowed
+    /// for the pool's ID type.
+    fn is_full(&self) -> bool {
+        // Symbol IDs range from 0 to M, where M is given by `I::max_value()`;
+        // hence the pool is full iff it has M + 1 slots and none are free.
+        self.free.is_empty()
+            && self.slots.len() > I::max_value().to_usize().unwrap_or(usize::MAX)
+    }
+}
+
+impl<T: ?Sized, I, S> crate::sym::Pool for Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId
+{
+    type Symbol = Sym<I>;
+
+    #[cfg(debug_assertions)]
+    fn id(&self) -> crate::sym::PoolId {
+        self.pool_id
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn create_symbol(&self, id: <Self::Symbol as crate::sym::Symbol>::Id) -> Self::Symbol {
+        Sym::create(id)
+    }
+
+    #[cfg(debug_assertions)]
+    fn create_symbol(&self, id: <Self::Symbol as crate::sym::Symbol>::Id) -> Self::Symbol {
+        Sym::create(id, self.id())
+    }
+}
+
+// Default
+impl<T: ?Sized, I, S> Default for Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash,
+          I: SymbolId,
+          S: BuildHasher + Default
+{
+    fn default() -> Self {
+        Pool::with_hasher(Default::default())
+    }
+}
+
+// Intern
+impl<T: ?Sized, I, S> Intern for &mut Pool<T, I, S>
+    where I: SymbolId,
+          T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          S: BuildHasher,
+{
+    type Input = T;
+    type Symbol = Sym<I>;
+
+    fn intern(self, value: &Self::Input) -> Result<Self::Symbol> {
+        let key = core::hash(&self.hash_builder, value);
+        if let Some(id) = self.find_id(key, value) {
+            return Ok(self.symbol_for(id));
+        }
+
+        let id = match self.free.pop() {
+            Some(id) => {
+                let idx = id.to_usize().expect("Unexpected failure to convert symbol ID to usize");
+                self.slots[idx].value = Some(value.to_owned());
+                id
+            },
+            None if self.is_full() => return Err(ErrorKind::PoolOverflow.into()),
+            None => {
+                self.slots.push(Slot{value: Some(value.to_owned()), generation: 0});
+                // We do not expect this conversion to fail, since `is_full()`
+                // checks if a new ID would be a representable value.
+                I::from_usize(self.slots.len() - 1)
+                    .expect("Unexpected failure to convert symbol ID from usize")
+            },
+        };
+        core::insert_id(&mut self.ids_map, key, id);
+        self.len += 1;
+
+        Ok(self.symbol_for(id))
+    }
+}
+
+// ----------------------------------------------------------------
+// Remove
+impl<T: ?Sized, I, S> Remove for &mut Pool<T, I, S>
+    where I: SymbolId,
+          T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          S: BuildHasher,
+{
+    type Symbol = Sym<I>;
+
+    fn remove(self, s: Self::Symbol) -> Result<()> {
+        check_matching_pool!(self, s);
+        self.slot(s)?;
+
+        let idx = s.id().to_usize().expect("Unexpected failure to convert symbol ID to usize");
+        let slot = &mut self.slots[idx];
+        let value = slot.value.take().expect("Unexpected missing value in occupied slot");
+        slot.generation = slot.generation.wrapping_add(1);
+        core::remove_id(&mut self.ids_map, core::hash(&self.hash_builder, value.borrow()), s.id());
+        self.free.push(s.id());
+        self.len -= 1;
+        Ok(())
+    }
+}
+
+// ----------------------------------------------------------------
+// Lookup
+impl<T: ?Sized, I, S> Lookup for &Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId,
+          S: BuildHasher,
+{
+    type Input = T;
+    type Symbol = Sym<I>;
+
+    fn get(self, value: &Self::Input) -> Option<Self::Symbol> {
+        let key = core::hash(&self.hash_builder, value);
+        self.find_id(key, value).map(|id| self.symbol_for(id))
+    }
+
+    fn contains_symbol(self, s: Self::Symbol) -> bool {
+        ! is_foreign_symbol!(self, s) && self.slot(s).is_ok()
+    }
+}
+
+// ----------------------------------------------------------------
+// Resolve
+impl<'a, T: ?Sized, I, S> Resolve for &'a Pool<T, I, S>
+    where T: ToOwned + Eq + Hash,
+          T::Owned: Eq + Hash + Borrow<T>,
+          I: SymbolId
+{
+    type Input = Sym<I>;
+    type Output = &'a T;
+
+    fn resolve(self, s: Self::Input) -> Result<Self::Output> {
+        check_matching_pool!(self, s);
+        self.slot(s).map(|slot| slot.value.as_ref().expect("Unexpected missing value in occupied slot").borrow())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Pool;
+    use crate::traits::*;
+    use crate::ErrorKind;
+
+    #[test]
+    fn reuses_ids_of_removed_values() {
+        let mut pool = Pool::<str, u8>::new();
+        let syms: Vec<_> = (0..256).map(|i| pool.intern(&i.to_string()).expect("failed to intern value")).collect();
+        assert!(pool.is_full());
+        assert_eq!(ErrorKind::PoolOverflow, pool.intern("overflow").unwrap_err().kind());
+
+        pool.remove(syms[17]).expect("failed to remove value");
+        assert!(! pool.is_full());
+        assert_eq!(255, pool.len());
+        assert_eq!(None, pool.get("17"));
+
+        let new = pool.intern("overflow").expect("failed to intern value into freed slot");
+        assert_eq!(Ok("overflow"), pool.resolve(new));
+        assert_eq!(Ok("18"), pool.resolve(syms[18]));
+        assert!(pool.is_full());
+    }
+
+    #[test]
+    fn rejects_stale_symbols() {
+        let mut pool = Pool::<str, u16>::new();
+        let foo = pool.intern("foo").expect("failed to intern value");
+        pool.remove(foo).expect("failed to remove value");
+        assert!(! pool.contains_symbol(foo));
+        assert!(! Pool::<str, u16>::new().contains_symbol(foo));
+        assert_eq!(ErrorKind::StaleSymbol, pool.resolve(foo).unwrap_err().kind());
+        assert_eq!(ErrorKind::StaleSymbol, pool.remove(foo).unwrap_err().kind());
+
+        // Re-interning the same value gives a new symbol with the same ID.
+        let foo2 = pool.intern("foo").expect("failed to intern value");
+        assert!(foo != foo2);
+        assert_eq!(Ok("foo"), pool.resolve(foo2));
+        assert_eq!(ErrorKind::StaleSymbol, pool.resolve(foo).unwrap_err().kind());
+        assert_eq!(Some(foo2), pool.get("foo"));
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright (C) 2016-2017 Symtern Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-Apache
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or
+// distributed except according to those terms.
+//! Translation of symbols from one pool's IDs to another's.
+use crate::traits::SymbolId;
+use crate::{Result, ErrorKind};
+use crate::basic::Sym;
+use crate::sym::Symbol as ISymbol;
+#[cfg(debug_assertions)]
+use crate::sym::PoolId;
+
+/// Table that translates symbols created by one pool into the corresponding
+/// symbols of another.
+///
+/// A `SymbolRemap` is returned by operations that change the symbol IDs
+/// assigned to values, like [`Pool::merge`] and [`Pool::retain`].
+/// Translating a symbol is a single table lookup, and whole slices of
+/// symbols can be translated in place with [`apply`](#method.apply).
+///
+/// [`Pool::merge`]: struct.Pool.html#method.merge
+/// [`Pool::retain`]: struct.Pool.html#method.retain
+#[derive(Clone, Debug)]
+pub struct SymbolRemap<I> {
+    /// New ID for each old ID, or `None` if the old ID's value is gone.
+    ids: Box<[Option<I>]>,
+    #[cfg(debug_assertions)]
+    from_pool: PoolId,
+    #[cfg(debug_assertions)]
+    to_pool: PoolId,
+}
+
+impl<I: SymbolId> SymbolRemap<I> {
+    /// Create a remap from a table of new IDs indexed by old ID, for
+    /// translating symbols from pool `from_pool` to pool `to_pool`.
+    #[cfg(debug_assertions)]
+    pub(crate) fn new(ids: Vec<Option<I>>, from_pool: PoolId, to_pool: PoolId) -> Self {
+        SymbolRemap{ids: ids.into_boxed_slice(), from_pool, to_pool}
+    }
+
+    /// Create a remap from a table of new IDs indexed by old ID.
+    #[cfg(not(debug_assertions))]
+    pub(crate) fn new(ids: Vec<Option<I>>) -> Self {
+        SymbolRemap{ids: ids.into_boxed_slice()}
+    }
+
+    /// ID of the pool whose symbols are translated, used by
+    /// `check_matching_pool!`.
+    #[cfg(debug_assertions)]
+    fn id(&self) -> PoolId {
+        self.from_pool
+    }
+
+    #[cfg(debug_assertions)]
+    fn create(&self, id: I) -> Sym<I> {
+        Sym::create(id, self.to_pool)
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn create(&self, id: I) -> Sym<I> {
+        Sym::create(id)
+    }
+
+    /// Find the new ID for a symbol.
+    fn new_id(&self, s: Sym<I>) -> Option<I> {
+        check_matching_pool!(self, s);
+        s.id().to_usize().and_then(|idx| self.ids.get(idx)).and_then(|&id| id)
+    }
+
+    /// Fetch the number of old symbol IDs covered by the remap.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Check if the remap covers no symbols at all.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Translate a single symbol.  Returns `None` if the symbol's value is not
+    /// present in the new pool.
+    pub fn get(&self, old: Sym<I>) -> Option<Sym<I>> {
+        self.new_id(old).map(|id| self.create(id))
+    }
+
+    /// Translate every symbol in a slice (or `Vec`) in place.
+    ///
+    /// If any symbol's value is not present in the new pool, fails with
+    /// `ErrorKind::NoSuchSymbol` and leaves the slice unchanged.
+    pub fn apply(&self, symbols: &mut [Sym<I>]) -> Result<()> {
+        if symbols.iter().any(|&s| self.new_id(s).is_none()) {
+            return Err(ErrorKind::NoSuchSymbol.into());
+        }
+        for s in symbols.iter_mut() {
+            let id = self.new_id(*s).expect("Unexpected missing ID in symbol remap");
+            *s = self.create(id);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,378 @@
+// Copyright (C) 2016-2017 Symtern Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-Apache
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or
+// distributed except according to those terms.
+//! Binary snapshot format for pools of strings and byte strings.
+//!
+//! A snapshot consists of a fixed-size header, the pool's values in order of
+//! symbol ID, and a trailing checksum.  All integers are little-endian.
+//!
+//! | Field      | Size     | Contents                                       |
+//! |------------|----------|------------------------------------------------|
+//! | magic      | 8 bytes  | `b"SYMTERN\0"`                                 |
+//! | version    | 2 bytes  | format version; currently 1                    |
+//! | ID width   | 1 byte   | size of the pool's symbol-ID type, in bytes    |
+//! | count      | 8 bytes  | number of values                               |
+//! | values     | variable | each value's length as a LEB128 varint, followed by its bytes |
+//! | checksum   | 8 bytes  | 64-bit FNV-1a hash of all preceding bytes      |
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::mem;
+
+use crate::traits::SymbolId;
+use crate::{Result, ErrorKind};
+
+/// Bytes identifying a file as a Symtern snapshot.
+pub const MAGIC: [u8; 8] = *b"SYMTERN\0";
+
+/// Version of the snapshot format written by this version of the crate.
+pub const VERSION: u16 = 1;
+
+/// Size of the snapshot header, in bytes.
+pub const HEADER_LEN: usize = 8 + 2 + 1 + 8;
+
+/// Size of the trailing checksum, in bytes.
+pub const CHECKSUM_LEN: usize = 8;
+
+/// Incremental 64-bit FNV-1a hasher used to checksum snapshots.
+#[derive(Copy, Clone, Debug)]
+pub struct Checksum(u64);
+
+impl Checksum {
+    pub fn new() -> Self {
+        Checksum(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= u64::from(b);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Snapshot header fields.
+#[derive(Copy, Clone, Debug)]
+pub struct Header {
+    /// Number of values stored in the snapshot.
+    pub count: u64,
+}
+
+impl Header {
+    /// Encode the header for a snapshot of `count` values with symbol-ID type
+    /// `I`.
+    fn encode<I: SymbolId>(count: u64) -> [u8; HEADER_LEN] {
+        let mut buf = [0u8; HEADER_LEN];
+        buf[..8].copy_from_slice(&MAGIC);
+        buf[8..10].copy_from_slice(&VERSION.to_le_bytes());
+        buf[10] = mem::size_of::<I>() as u8;
+        buf[11..].copy_from_slice(&count.to_le_bytes());
+        buf
+    }
+
+    /// Decode and validate a header for a snapshot of values with symbol-ID
+    /// type `I`.
+    pub fn decode<I: SymbolId>(buf: &[u8; HEADER_LEN]) -> Result<Header> {
+        if buf[..8] != MAGIC {
+            return Err(ErrorKind::CorruptSnapshot.into());
+        }
+        if u16::from_le_bytes([buf[8], buf[9]]) != VERSION {
+            return Err(ErrorKind::UnsupportedSnapshotVersion.into());
+        }
+        if usize::from(buf[10]) != mem::size_of::<I>() {
+            return Err(ErrorKind::IdWidthMismatch.into());
+        }
+        let mut count = [0u8; 8];
+        count.copy_from_slice(&buf[11..]);
+        let count = u64::from_le_bytes(count);
+
+        // Symbol IDs range from 0 to `I::max_value()`, so a valid snapshot
+        // never holds more than `I::max_value() + 1` values.
+        match I::max_value().to_u64() {
+            Some(max) if count > 0 && count - 1 > max => Err(ErrorKind::CorruptSnapshot.into()),
+            _ => Ok(Header{count}),
+        }
+    }
+}
+
+/// Encode `n` as a LEB128 varint, returning the buffer and the number of
+/// bytes used.
+fn encode_varint(mut n: u64) -> ([u8; 10], usize) {
+    let mut buf = [0u8; 10];
+    let mut i = 0;
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            buf[i] = byte;
+            return (buf, i + 1);
+        }
+        buf[i] = byte | 0x80;
+        i += 1;
+    }
+}
+
+/// Decode a LEB128 varint from the front of a slice, returning its value and
+/// the number of bytes it occupied.
+pub fn decode_varint(bytes: &[u8]) -> Result<(u64, usize)> {
+    let mut n = 0u64;
+    for (i, &byte) in bytes.iter().enumerate().take(10) {
+        let bits = u64::from(byte & 0x7f);
+        if i == 9 && bits > 1 {
+            return Err(ErrorKind::CorruptSnapshot.into());
+        }
+        n |= bits << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((n, i + 1));
+        }
+    }
+    if bytes.len() < 10 {
+        Err(ErrorKind::TruncatedSnapshot.into())
+    } else {
+        Err(ErrorKind::CorruptSnapshot.into())
+    }
+}
+
+/// Writer adaptor that keeps a running checksum of everything written.
+struct ChecksumWriter<'a, W: 'a + ?Sized> {
+    inner: &'a mut W,
+    checksum: Checksum,
+}
+
+impl<'a, W: ?Sized + Write> ChecksumWriter<'a, W> {
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.checksum.update(bytes);
+        self.inner.write_all(bytes)
+    }
+}
+
+/// Write a snapshot of the given values, which must be in order of symbol ID,
+/// to `writer`.
+pub fn write<'a, I, W, It>(writer: &mut W, count: usize, values: It) -> io::Result<()>
+    where I: SymbolId,
+          W: ?Sized + Write,
+          It: IntoIterator<Item=&'a [u8]>
+{
+    let mut w = ChecksumWriter{inner: writer, checksum: Checksum::new()};
+    w.write_all(&Header::encode::<I>(count as u64))?;
+    for value in values {
+        let (len, n) = encode_varint(value.len() as u64);
+        w.write_all(&len[..n])?;
+        w.write_all(value)?;
+    }
+    let checksum = w.checksum.value();
+    w.inner.write_all(&checksum.to_le_bytes())
+}
+
+/// Reader adaptor that keeps a running checksum of everything read, and
+/// converts I/O errors to our own error type.
+struct ChecksumReader<R> {
+    inner: R,
+    checksum: Checksum,
+}
+
+impl<R: Read> ChecksumReader<R> {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        read_exact(&mut self.inner, buf)?;
+        self.checksum.update(buf);
+        Ok(())
+    }
+
+    fn read_varint(&mut self) -> Result<u64> {
+        let mut buf = [0u8; 10];
+        for i in 0..buf.len() {
+            self.read_exact(&mut buf[i..i + 1])?;
+            if buf[i] & 0x80 == 0 {
+                return decode_varint(&buf[..i + 1]).map(|(n, _)| n);
+            }
+        }
+        Err(ErrorKind::CorruptSnapshot.into())
+    }
+
+    /// Read a value of the given length without trusting the length enough to
+    /// allocate it all up front.
+    fn read_value(&mut self, len: u64) -> Result<Vec<u8>> {
+        let mut value = Vec::new();
+        match (&mut self.inner).take(len).read_to_end(&mut value) {
+            Ok(n) if n as u64 == len => {
+                self.checksum.update(&value);
+                Ok(value)
+            },
+            Ok(_) => Err(ErrorKind::TruncatedSnapshot.into()),
+            Err(_) => Err(ErrorKind::SnapshotReadFailed.into()),
+        }
+    }
+}
+
+/// Fill `buf` from `reader`, mapping I/O errors to our own error type.
+fn read_exact<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<()> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => ErrorKind::TruncatedSnapshot.into(),
+        _ => ErrorKind::SnapshotReadFailed.into(),
+    })
+}
+
+/// Read a snapshot of values with symbol-ID type `I` from `reader`, passing
+/// each value to `f` in order of symbol ID.  The values must not be used
+/// until this function returns successfully, since the checksum is verified
+/// only after every value has been read.
+pub fn read<I, R, F>(reader: R, mut f: F) -> Result<()>
+    where I: SymbolId,
+          R: Read,
+          F: FnMut(Vec<u8>) -> Result<()>
+{
+    let mut r = ChecksumReader{inner: reader, checksum: Checksum::new()};
+    let mut header = [0u8; HEADER_LEN];
+    r.read_exact(&mut header)?;
+    let header = Header::decode::<I>(&header)?;
+
+    for _ in 0..header.count {
+        let len = r.read_varint()?;
+        f(r.read_value(len)?)?;
+    }
+
+    let mut checksum = [0u8; CHECKSUM_LEN];
+    read_exact(&mut r.inner, &mut checksum)?;
+    if u64::from_le_bytes(checksum) != r.checksum.value() {
+        return Err(ErrorKind::CorruptSnapshot.into());
+    }
+    Ok(())
+}
+
+/// Validate a snapshot of values with symbol-ID type `I` held in memory,
+/// passing the offset of each value's record, and the value itself, to `f` in
+/// order of symbol ID.  Bytes following the checksum are not allowed.
+pub fn parse<'a, I, F>(bytes: &'a [u8], mut f: F) -> Result<()>
+    where I: SymbolId,
+          F: FnMut(usize, &'a [u8]) -> Result<()>
+{
+    if bytes.len() < HEADER_LEN + CHECKSUM_LEN {
+        return Err(ErrorKind::TruncatedSnapshot.into());
+    }
+    let mut header = [0u8; HEADER_LEN];
+    header.copy_from_slice(&bytes[..HEADER_LEN]);
+    let header = Header::decode::<I>(&header)?;
+
+    let mut offset = HEADER_LEN;
+    let data_end = bytes.len() - CHECKSUM_LEN;
+    for _ in 0..header.count {
+        let (value, next) = record_at(&bytes[..data_end], offset)?;
+        f(offset, value)?;
+        offset = next;
+    }
+    if offset != data_end {
+        return Err(ErrorKind::CorruptSnapshot.into());
+    }
+
+    let mut checksum = Checksum::new();
+    checksum.update(&bytes[..data_end]);
+    let mut expected = [0u8; CHECKSUM_LEN];
+    expected.copy_from_slice(&bytes[data_end..]);
+    if u64::from_le_bytes(expected) != checksum.value() {
+        return Err(ErrorKind::CorruptSnapshot.into());
+    }
+    Ok(())
+}
+
+/// Fetch the value whose record starts at `offset`, and the offset of the
+/// record that follows it.
+pub fn record_at(bytes: &[u8], offset: usize) -> Result<(&[u8], usize)> {
+    let (len, n) = decode_varint(&bytes[offset..])?;
+    let start = offset + n;
+    match usize::try_from(len).ok().and_then(|len| start.checked_add(len)) {
+        Some(end) if end <= bytes.len() => Ok((&bytes[start..end], end)),
+        _ => Err(ErrorKind::TruncatedSnapshot.into()),
+    }
+}
+
+/// Types whose values can be stored in a snapshot.
+pub trait Value {
+    /// Reinterpret a value's stored bytes, or return `None` if they are not
+    /// a valid value of this type.
+    fn from_bytes(bytes: &[u8]) -> Option<&Self>;
+
+    /// Reinterpret a value's stored bytes without validating them.
+    ///
+    /// # Safety
+    ///
+    /// `bytes` must have previously been accepted by `from_bytes`.
+    unsafe fn from_bytes_unchecked(bytes: &[u8]) -> &Self;
+}
+
+impl Value for [u8] {
+    fn from_bytes(bytes: &[u8]) -> Option<&Self> {
+        Some(bytes)
+    }
+
+    unsafe fn from_bytes_unchecked(bytes: &[u8]) -> &Self {
+        bytes
+    }
+}
+
+impl Value for str {
+    fn from_bytes(bytes: &[u8]) -> Option<&Self> {
+        ::std::str::from_utf8(bytes).ok()
+    }
+
+    unsafe fn from_bytes_unchecked(bytes: &[u8]) -> &Self {
+        ::std::str::from_utf8_unchecked(bytes)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_varint, encode_varint, parse, read, write};
+    use crate::ErrorKind;
+
+    #[test]
+    fn varints_round_trip() {
+        for &n in &[0, 1, 127, 128, 300, 1 << 35, u64::MAX] {
+            let (buf, len) = encode_varint(n);
+            assert_eq!(Ok((n, len)), decode_varint(&buf[..len]));
+            if len > 1 {
+                assert_eq!(ErrorKind::TruncatedSnapshot, decode_varint(&buf[..len - 1]).unwrap_err().kind());
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_damaged_snapshots() {
+        let mut snapshot = Vec::new();
+        write::<u16, _, _>(&mut snapshot, 2, vec![&b"foo"[..], &b"quux"[..]]).unwrap();
+
+        let read_all = |bytes: &[u8]| {
+            let mut values = Vec::new();
+            read::<u16, _, _>(bytes, |v| { values.push(v); Ok(()) }).map(|_| values)
+        };
+        assert_eq!(Ok(vec![b"foo".to_vec(), b"quux".to_vec()]), read_all(&snapshot));
+
+        let parse_all = |bytes: &[u8]| {
+            let mut values = Vec::new();
+            parse::<u16, _>(bytes, |_, v| { values.push(v.to_vec()); Ok(()) }).map(|_| values)
+        };
+        assert_eq!(read_all(&snapshot), parse_all(&snapshot));
+
+        // Every proper prefix of the snapshot is truncated.
+        for len in 0..snapshot.len() {
+            assert_eq!(Err(ErrorKind::TruncatedSnapshot), read_all(&snapshot[..len]).map_err(|e| e.kind()),
+                       "prefix of length {}", len);
+            assert!(parse_all(&snapshot[..len]).is_err(), "prefix of length {}", len);
+        }
+
+        // Flipping any single bit is detected.
+        for i in 0..snapshot.len() * 8 {
+            let mut damaged = snapshot.clone();
+            damaged[i / 8] ^= 1 << (i % 8);
+            assert!(read_all(&damaged).is_err(), "bit {} flipped", i);
+            assert!(parse_all(&damaged).is_err(), "bit {} flipped", i);
+        }
+    }
+}
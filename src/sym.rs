@@ -13,11 +13,93 @@
 //! create symbols out of thin air and inspect implementation details, Bad
 //! Things™ are likely to happen if you use their methods in other contexts.
 
+#[cfg(feature = "serde")] use std::cell::RefCell;
+#[cfg(debug_assertions)] use std::sync::atomic::{AtomicUsize, Ordering};
+
 use crate::traits::{self, SymbolId};
 
 /// Type that will be used for `Pool::Id` in all generated `Pool` impls.
 pub type PoolId = usize;
 
+#[cfg(debug_assertions)]
+static NEXT_POOL_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Allocate a pool ID that has not been given to any other pool.
+#[cfg(debug_assertions)]
+pub fn next_pool_id() -> PoolId {
+    NEXT_POOL_ID.fetch_add(1, Ordering::SeqCst)
+}
+
+#[cfg(feature = "serde")]
+thread_local! {
+    /// Pools named by active `deserialize_symbols` calls, innermost last.
+    static DESERIALIZING_FOR: RefCell<Vec<PoolId>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Fetch the pool ID that symbols deserialized for the given pool should
+/// carry.  Pool IDs exist only in debug builds; release builds use zero.
+#[cfg(feature = "serde")]
+pub fn deserialization_id<P: Pool>(pool: &P) -> PoolId {
+    #[cfg(debug_assertions)]
+    return pool.id();
+    #[cfg(not(debug_assertions))]
+    return { let _ = pool; 0 };
+}
+
+/// Removes the innermost pool when a `deserialize_symbols` scope ends, even
+/// if it ends by panicking.
+#[cfg(feature = "serde")]
+struct DeserializeGuard;
+
+#[cfg(feature = "serde")]
+impl Drop for DeserializeGuard {
+    fn drop(&mut self) {
+        DESERIALIZING_FOR.with(|pools| pools.borrow_mut().pop());
+    }
+}
+
+/// Call `f` with symbols deserialized on the current thread bound to the pool
+/// with the given ID (as returned by `deserialization_id`), and return its
+/// result.  This backs the public `deserialize_symbols` methods of pool and
+/// adaptor types.
+#[cfg(feature = "serde")]
+pub fn deserialize_symbols<F, T>(pool_id: PoolId, f: F) -> T
+    where F: FnOnce() -> T
+{
+    DESERIALIZING_FOR.with(|pools| pools.borrow_mut().push(pool_id));
+    let _guard = DeserializeGuard;
+    f()
+}
+
+/// Serialize a symbol as its raw ID.
+#[cfg(feature = "serde")]
+pub fn serialize<Sym, S>(sym: &Sym, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+    where Sym: Symbol,
+          Sym::Id: serde::Serialize,
+          S: serde::Serializer
+{
+    serde::Serialize::serialize(sym.id_ref(), serializer)
+}
+
+/// Deserialize a symbol from its raw ID, binding it to the pool named by the
+/// innermost active `deserialize_symbols` call.  Fails if there is no such
+/// call.
+#[cfg(feature = "serde")]
+pub fn deserialize<'de, Sym, D>(deserializer: D) -> ::std::result::Result<Sym, D::Error>
+    where Sym: Symbol,
+          Sym::Id: serde::Deserialize<'de>,
+          D: serde::Deserializer<'de>
+{
+    let id = <Sym::Id as serde::Deserialize>::deserialize(deserializer)?;
+    let pool_id = DESERIALIZING_FOR.with(|pools| pools.borrow().last().cloned())
+        .ok_or_else(|| <D::Error as serde::de::Error>::custom(
+            "symbols can only be deserialized within a pool's `deserialize_symbols` call"))?;
+    #[cfg(debug_assertions)]
+    return Ok(Sym::create(id, pool_id));
+    #[cfg(not(debug_assertions))]
+    return { let _ = pool_id; Ok(Sym::create(id)) };
+}
+
 /// Types used by interner implementations.
 pub trait Types {
     /// Symbol type associated with the pool; this should be the same as the
@@ -75,6 +157,28 @@ pub trait Symbol: traits::Symbol {
     fn create(id: Self::Id) -> Self;
 }
 
+/// Type of the generation counters carried by generational symbols.
+pub type Generation = u32;
+
+/// Interface for symbols that carry a generation counter in addition to their
+/// ID.  Pools that reuse the IDs of removed values increment the counter for
+/// an ID each time it is freed, so that symbols for the removed value can be
+/// told apart from symbols for the ID's new value.
+///
+/// Symbols created with `Symbol::create` have generation zero.
+pub trait Generational: Symbol {
+    /// Fetch the symbol's generation.
+    fn generation(&self) -> Generation;
+
+    /// Create a new symbol with the given ID, generation, and source pool.
+    #[cfg(debug_assertions)]
+    fn create_with_generation(id: Self::Id, generation: Generation, pool_id: PoolId) -> Self;
+
+    /// Create a new symbol with the given ID and generation.
+    #[cfg(not(debug_assertions))]
+    fn create_with_generation(id: Self::Id, generation: Generation) -> Self;
+}
+
 impl<'a, T> Types for &'a T
     where T: Types
 {
@@ -91,6 +195,42 @@ impl<'a, T> Types for &'a mut T
     type Output = T::Output;
 }
 
+/// Panic if a symbol was not created by the given pool.  This check is
+/// performed only when the crate is compiled in debug mode.
+#[cfg(debug_assertions)]
+macro_rules! check_matching_pool {
+    ($slf: ident, $sym: ident) => {
+        if $sym.pool_id() != $slf.id() {
+            panic!(concat!("\nDetected an invalid attempt to resolve a symbol on a pool that did not\n",
+                           "create it.  This is a bug in the program or library using Symtern; do not\n",
+                           "report it to the Symtern developers."));
+        }
+    };
+}
+
+/// Panic if a symbol was not created by the given pool.  This check is
+/// performed only when the crate is compiled in debug mode.
+#[cfg(not(debug_assertions))]
+macro_rules! check_matching_pool {
+    ($slf: ident, $sym: ident) => {};
+}
+
+/// Check if a symbol was created by a pool other than the given one.  This
+/// check is performed only when the crate is compiled in debug mode; in
+/// release mode, every symbol is assumed to belong to the pool.
+#[cfg(debug_assertions)]
+macro_rules! is_foreign_symbol {
+    ($slf: ident, $sym: ident) => ( $sym.pool_id() != $slf.id() );
+}
+
+/// Check if a symbol was created by a pool other than the given one.  This
+/// check is performed only when the crate is compiled in debug mode; in
+/// release mode, every symbol is assumed to belong to the pool.
+#[cfg(not(debug_assertions))]
+macro_rules! is_foreign_symbol {
+    ($slf: ident, $sym: ident) => ( false );
+}
+
 /// Define an opaque type constructor wrapping an underlying primitive ID, or
 /// other symbol type, to be used as a symbol type.  When wrapping a primitive
 /// ID type, the mandatory type parameter is automatically bounded by
@@ -115,19 +255,33 @@ impl<'a, T> Types for &'a mut T
 ///     pub WrapperSym<W>(W): "Wraps `W` for extra hugs.";
 /// }
 /// ```
+///
+/// Symbols for pools that reuse the IDs of removed values need to carry
+/// a generation counter as well; mark such types with `[generation]`.  The
+/// counter is available via the private `generation` field and the
+/// [`Generational`] trait, which wrapper types also implement whenever their
+/// wrapped type does.
+///
+/// ```rust,ignore
+/// make_sym! {
+///     pub RecycledSym<I> [generation]: "Symbol that can tell when its value has been removed.";
+/// }
+/// ```
+///
+/// [`Generational`]: trait.Generational.html
 macro_rules! make_sym {
     () => {};
 
     // @impl for wrapped symbol types
     (@impl $name:ident < $I: ident > ( $wrapped: path ) ; $($bound: tt)+ ) => {
-        impl<$I> ::sym::Symbol for $name<$I>
+        impl<$I> crate::sym::Symbol for $name<$I>
             where $I: $($bound)+,
-                  $wrapped: ::sym::Symbol
+                  $wrapped: crate::sym::Symbol
         {
-            type Id = <$wrapped as ::sym::Symbol>::Id;
+            type Id = <$wrapped as crate::sym::Symbol>::Id;
 
             #[cfg(debug_assertions)]
-            fn pool_id(&self) -> ::sym::PoolId {
+            fn pool_id(&self) -> crate::sym::PoolId {
                 self.wrapped.pool_id()
             }
 
@@ -136,12 +290,29 @@ macro_rules! make_sym {
 
             #[cfg(not(debug_assertions))]
             fn create(id: Self::Id) -> Self {
-                $name{wrapped: <$wrapped as ::sym::Symbol>::create(id)}
+                $name{wrapped: <$wrapped as crate::sym::Symbol>::create(id)}
+            }
+
+            #[cfg(debug_assertions)]
+            fn create(id: Self::Id, pool_id: crate::sym::PoolId) -> Self {
+                $name{wrapped: <$wrapped as crate::sym::Symbol>::create(id, pool_id)}
+            }
+        }
+
+        impl<$I> crate::sym::Generational for $name<$I>
+            where $I: $($bound)+,
+                  $wrapped: crate::sym::Generational
+        {
+            fn generation(&self) -> crate::sym::Generation { self.wrapped.generation() }
+
+            #[cfg(not(debug_assertions))]
+            fn create_with_generation(id: Self::Id, generation: crate::sym::Generation) -> Self {
+                $name{wrapped: <$wrapped as crate::sym::Generational>::create_with_generation(id, generation)}
             }
 
             #[cfg(debug_assertions)]
-            fn create(id: Self::Id, pool_id: ::sym::PoolId) -> Self {
-                $name{wrapped: <$wrapped as ::sym::Symbol>::create(id, pool_id)}
+            fn create_with_generation(id: Self::Id, generation: crate::sym::Generation, pool_id: crate::sym::PoolId) -> Self {
+                $name{wrapped: <$wrapped as crate::sym::Generational>::create_with_generation(id, generation, pool_id)}
             }
         }
 
@@ -178,12 +349,84 @@ macro_rules! make_sym {
                 $name{id: id, pool_id: pool_id}
             }
         }
+
+        impl<$I> ::std::fmt::Debug for $name<$I>
+            where $I: $($bound)+ + ::std::fmt::Debug + 'static
+        {
+            /// Format the symbol as `Name(id = value)` if a resolver for it
+            /// is in scope (see `symtern::fmt::with_resolver`), or as a plain
+            /// struct otherwise.
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                crate::fmt::fmt_symbol(self, stringify!($name), &self.id, f).unwrap_or_else(|| {
+                    let mut s = f.debug_struct(stringify!($name));
+                    s.field("id", &self.id);
+                    #[cfg(debug_assertions)]
+                    s.field("pool_id", &self.pool_id);
+                    s.finish()
+                })
+            }
+        }
+    };
+
+    // @impl for generational symbol types
+    (@impl $name:ident < $I: ident > [generation] ; $($bound: tt)+) => {
+        impl<$I> crate::sym::Symbol for $name<$I>
+            where $I: $($bound)+
+        {
+            type Id = $I;
+
+            #[cfg(debug_assertions)]
+            fn pool_id(&self) -> crate::sym::PoolId {
+                self.pool_id
+            }
+
+            fn id(&self) -> Self::Id { self.id }
+            fn id_ref(&self) -> &Self::Id { &self.id }
+            #[cfg(not(debug_assertions))]
+            fn create(id: Self::Id) -> Self {
+                $name{id, generation: 0}
+            }
+            #[cfg(debug_assertions)]
+            fn create(id: Self::Id, pool_id: crate::sym::PoolId) -> Self {
+                $name{id, generation: 0, pool_id}
+            }
+        }
+
+        impl<$I> crate::sym::Generational for $name<$I>
+            where $I: $($bound)+
+        {
+            fn generation(&self) -> crate::sym::Generation { self.generation }
+
+            #[cfg(not(debug_assertions))]
+            fn create_with_generation(id: Self::Id, generation: crate::sym::Generation) -> Self {
+                $name{id, generation}
+            }
+            #[cfg(debug_assertions)]
+            fn create_with_generation(id: Self::Id, generation: crate::sym::Generation, pool_id: crate::sym::PoolId) -> Self {
+                $name{id, generation, pool_id}
+            }
+        }
+
+        impl<$I> ::std::fmt::Debug for $name<$I>
+            where $I: $($bound)+ + ::std::fmt::Debug + 'static
+        {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                crate::fmt::fmt_symbol(self, stringify!($name), &self.id, f).unwrap_or_else(|| {
+                    let mut s = f.debug_struct(stringify!($name));
+                    s.field("id", &self.id);
+                    s.field("generation", &self.generation);
+                    #[cfg(debug_assertions)]
+                    s.field("pool_id", &self.pool_id);
+                    s.finish()
+                })
+            }
+        }
     };
 
     // @struct for wrapped symbol types
     (@struct $name:ident < $I: ident > ( $wrapped: path ) : $doc:expr ; $($bound: tt)+) => {
         #[doc = $doc]
-        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
         pub struct $name<$I: $($bound)+ > {
             wrapped: $wrapped,
         }
@@ -193,19 +436,38 @@ macro_rules! make_sym {
     (@struct $name:ident < $I: ident > : $doc:expr; $($bound: tt)+) => {
         #[doc = $doc]
         #[cfg(not(debug_assertions))]
-        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+        #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
         pub struct $name<$I: $($bound)+> {
             id: $I,
         }
         #[doc = $doc]
         #[cfg(debug_assertions)]
-        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+        #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
         pub struct $name<$I: $($bound)+> {
             id: $I,
             pool_id: crate::sym::PoolId,
         }
     };
 
+    // @struct for generational symbol types
+    (@struct $name:ident < $I: ident > [generation] : $doc:expr; $($bound: tt)+) => {
+        #[doc = $doc]
+        #[cfg(not(debug_assertions))]
+        #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name<$I: $($bound)+> {
+            id: $I,
+            generation: crate::sym::Generation,
+        }
+        #[doc = $doc]
+        #[cfg(debug_assertions)]
+        #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name<$I: $($bound)+> {
+            id: $I,
+            generation: crate::sym::Generation,
+            pool_id: crate::sym::PoolId,
+        }
+    };
+
     // Entry point for unwrapped symbol types
     ($(#[$attr: meta])*
      pub $name:ident < $I:ident $(: $bound: ident $(+ $rbound: ident)*)* > : $doc: expr; $($rest: tt)*)
@@ -223,4 +485,13 @@ macro_rules! make_sym {
             $(#[$attr])*
             make_sym!(@impl $name<$I>($wrapped) ; SymbolId $(+ $bound $( + $rbound)*)*);
             make_sym!($($rest)*); };
+
+    // Entry point for generational symbol types
+    ($(#[$attr: meta])*
+     pub $name:ident < $I:ident $(: $bound: ident $(+ $rbound: ident)*)* > [generation] : $doc: expr; $($rest: tt)*)
+        => {$(#[$attr])*
+            make_sym!(@struct $name<$I> [generation] : $doc; SymbolId $(+ $bound $( + $rbound)*)*);
+            $(#[$attr])*
+            make_sym!(@impl $name<$I> [generation] ; SymbolId $(+ $bound $( + $rbound)*)*);
+            make_sym!($($rest)*); };
 }
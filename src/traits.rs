@@ -142,7 +142,8 @@
 //! ```
 //!
 //! Because `clear` borrows `self` mutably, it cannot be called until all
-//! immutable borrows held by `Sym<'a>` instances have ended.
+//! immutable borrows held by `Sym<'a>` instances have ended.  This is how the
+//! [`Clear`] trait behaves when implemented by the [`Luma`] adaptor.
 //!
 //! ## Footnotes
 //!
@@ -154,6 +155,8 @@
 //! [indexing] crate.
 //!
 //! [indexing]: https://github.com/bluss/indexing
+//! [`Clear`]: trait.Clear.html
+//! [`Luma`]: ../adaptors/struct.Luma.html
 //! [`intern`]: trait.Intern.html#tymethod.intern
 //! [`Intern`]: trait.Intern.html
 //! [`Resolve`]: trait.Resolve.html
@@ -161,15 +164,108 @@
 //! [Resolve::Input]: trait.Resolve.html#associatedtype.Input
 //! [Scala's path-dependent types]: http://danielwestheide.com/blog/2013/02/13/the-neophytes-guide-to-scala-part-13-path-dependent-types.html
 use std::hash::Hash;
-use ::num_traits::{Bounded, Unsigned, FromPrimitive, ToPrimitive};
+use std::iter;
+use std::num::{NonZeroU16, NonZeroU32, NonZeroU64};
+use ::num_traits::{Bounded, FromPrimitive, ToPrimitive};
 
 use super::Result;
 
 // ----------------------------------------------------------------
 
+mod sealed {
+    /// Keeps `SymbolId` from being implemented outside this crate.
+    pub trait Sealed {}
+}
+
 /// Trait describing primitive types used as symbols' internal representations.
-pub trait SymbolId: Copy + Eq + Hash + Bounded + Unsigned + FromPrimitive + ToPrimitive {}
-impl<T> SymbolId for T where T: Copy + Eq + Hash + Bounded + Unsigned + FromPrimitive + ToPrimitive {}
+///
+/// Implemented for the unsigned integer types, and for `NonZeroU16`,
+/// `NonZeroU32` and `NonZeroU64`.  A non-zero ID stores its symbol's index
+/// plus one, so that `Option`-wrapped symbols take no more space than the
+/// symbols themselves; in exchange, it can represent one fewer symbol than
+/// its plain counterpart.
+///
+/// ```rust
+/// use std::mem::size_of;
+/// use std::num::NonZeroU32;
+/// use symtern::prelude::*;
+/// use symtern::{Pool, Sym};
+///
+/// let mut pool = Pool::<str, NonZeroU32>::new();
+/// let sym = pool.intern("foo").expect("failed to intern a value");
+/// assert_eq!(Ok("foo"), pool.resolve(sym));
+///
+/// assert_eq!(size_of::<Sym<NonZeroU32>>(), size_of::<Option<Sym<NonZeroU32>>>());
+/// ```
+pub trait SymbolId: Copy + Eq + Hash + sealed::Sealed {
+    /// Fetch the ID with the largest index the type can represent.
+    fn max_value() -> Self;
+
+    /// Fetch the index represented by the ID, or `None` if it does not fit
+    /// in a `usize`.
+    fn to_usize(&self) -> Option<usize>;
+
+    /// Create the ID that represents the given index, or `None` if the type
+    /// cannot represent it.
+    fn from_usize(index: usize) -> Option<Self>;
+
+    /// Fetch the index represented by the ID, or `None` if it does not fit
+    /// in a `u64`.
+    fn to_u64(&self) -> Option<u64>;
+}
+
+macro_rules! impl_symbol_id {
+    ($($T: ty),*) => {$(
+        impl sealed::Sealed for $T {}
+
+        impl SymbolId for $T {
+            fn max_value() -> Self {
+                <$T as Bounded>::max_value()
+            }
+
+            fn to_usize(&self) -> Option<usize> {
+                ToPrimitive::to_usize(self)
+            }
+
+            fn from_usize(index: usize) -> Option<Self> {
+                FromPrimitive::from_usize(index)
+            }
+
+            fn to_u64(&self) -> Option<u64> {
+                ToPrimitive::to_u64(self)
+            }
+        }
+    )*}
+}
+impl_symbol_id!(u8, u16, u32, u64, u128, usize);
+
+/// Non-zero IDs store their index plus one.
+macro_rules! impl_nonzero_symbol_id {
+    ($($N: ty: $U: ty),*) => {$(
+        impl sealed::Sealed for $N {}
+
+        impl SymbolId for $N {
+            fn max_value() -> Self {
+                <$N>::MAX
+            }
+
+            fn to_usize(&self) -> Option<usize> {
+                SymbolId::to_usize(&(self.get() - 1))
+            }
+
+            fn from_usize(index: usize) -> Option<Self> {
+                <$U as SymbolId>::from_usize(index)
+                    .and_then(|index| index.checked_add(1))
+                    .and_then(<$N>::new)
+            }
+
+            fn to_u64(&self) -> Option<u64> {
+                SymbolId::to_u64(&(self.get() - 1))
+            }
+        }
+    )*}
+}
+impl_nonzero_symbol_id!(NonZeroU16: u16, NonZeroU32: u32, NonZeroU64: u64);
 
 /// Trait bounds for symbol (interned stand-in value) types.
 pub trait Symbol: Copy + Eq + Hash {}
@@ -229,6 +325,149 @@ pub trait Resolve {
     /// };
     /// ```
     fn resolve(self, symbol: Self::Input) -> Result<Self::Output>;
+
+    /// Wrap a symbol in a value that formats the symbol's referent with
+    /// `Display` or `Debug`.
+    ///
+    /// ```rust
+    /// use symtern::prelude::*;
+    /// use symtern::Pool;
+    ///
+    /// let mut pool = Pool::<str, u32>::new();
+    /// let sym = pool.intern("foo").expect("failed to intern a value");
+    /// assert_eq!("sym = foo", format!("sym = {}", pool.display(sym)));
+    /// ```
+    fn display(self, symbol: Self::Input) -> crate::fmt::Display<Self>
+        where Self: Sized
+    {
+        crate::fmt::Display::new(self, symbol)
+    }
+}
+
+
+/// Interface for interners that can look up the symbol for a value without
+/// interning it.
+///
+/// Unlike [`Intern`], this trait never modifies the interner, so it is
+/// implemented for `&'a T` even when interning requires `&'a mut T`.  Like
+/// [`Intern`] and [`Resolve`], its methods take `self` by value.
+///
+/// ```rust
+/// use symtern::prelude::*;
+/// use symtern::Pool;
+///
+/// let mut pool = Pool::<str, u32>::new();
+/// assert_eq!(None, pool.get("fn"));
+///
+/// let sym = pool.intern("fn").expect("failed to intern a value");
+/// assert_eq!(Some(sym), pool.get("fn"));
+/// assert!(pool.contains_symbol(sym));
+/// ```
+///
+/// [`Intern`]: trait.Intern.html
+/// [`Resolve`]: trait.Resolve.html
+pub trait Lookup {
+    /// Type of value accepted by `get`.
+    type Input: ?Sized;
+
+    /// Type used to represent interned values.
+    type Symbol: Symbol;
+
+    /// Fetch the symbol that corresponds to the given value, or `None` if the
+    /// value has not been interned.
+    fn get(self, value: &Self::Input) -> Option<Self::Symbol>;
+
+    /// Check if the given symbol can be resolved by the interner.  Returns
+    /// `false` rather than panicking for a symbol created by another
+    /// interner, which can only be detected in debug mode.
+    fn contains_symbol(self, symbol: Self::Symbol) -> bool;
+}
+
+
+/// Iterator returned by [`Iterate::symbols`](trait.Iterate.html#method.symbols).
+pub type Symbols<It, S, O> = iter::Map<It, fn((S, O)) -> S>;
+
+/// Iterator returned by [`Iterate::values`](trait.Iterate.html#method.values).
+pub type Values<It, S, O> = iter::Map<It, fn((S, O)) -> O>;
+
+/// Interface for interners that can enumerate the values they contain.
+///
+/// Like [`Resolve`], this trait's methods take `self` by value; for a given
+/// type `T`, the trait should be implemented for `&'a T`.
+///
+/// ```rust
+/// use symtern::prelude::*;
+/// use symtern::Pool;
+///
+/// let mut pool = Pool::<str, u32>::new();
+/// let foo = pool.intern("foo").expect("failed to intern a value");
+/// let bar = pool.intern("bar").expect("failed to intern a value");
+///
+/// assert_eq!(vec![(foo, "foo"), (bar, "bar")], pool.iter().collect::<Vec<_>>());
+/// ```
+///
+/// [`Resolve`]: trait.Resolve.html
+pub trait Iterate: Sized {
+    /// Type used to represent interned values.
+    type Symbol: Symbol;
+
+    /// Type through which each stored value is made available; this should
+    /// generally be the same as the `Output` type of the interner's
+    /// [`Resolve`] implementation.
+    ///
+    /// [`Resolve`]: trait.Resolve.html
+    type Output;
+
+    /// Type of iterator returned by [`iter`](#tymethod.iter).
+    type Iter: Iterator<Item=(Self::Symbol, Self::Output)>;
+
+    /// Create an iterator over each stored value and its symbol.
+    fn iter(self) -> Self::Iter;
+
+    /// Create an iterator over the symbols for each stored value.
+    fn symbols(self) -> Symbols<Self::Iter, Self::Symbol, Self::Output> {
+        self.iter().map(|(sym, _)| sym)
+    }
+
+    /// Create an iterator over each stored value.
+    fn values(self) -> Values<Self::Iter, Self::Symbol, Self::Output> {
+        self.iter().map(|(_, value)| value)
+    }
+}
+
+
+/// Interface for interners that can remove previously-interned values.
+///
+/// Like [`Intern`], this trait's methods take `self` by value; for a given
+/// type `T`, the trait should be implemented for `&'a mut T` or `&'a T`.
+///
+/// Implementations that reuse the IDs of removed values should use
+/// generation-checked symbols, so that resolving a symbol for a removed value
+/// fails with `ErrorKind::StaleSymbol` instead of yielding whatever value
+/// later took its place.
+///
+/// ```rust
+/// use symtern::prelude::*;
+/// use symtern::{RecyclingPool, ErrorKind};
+///
+/// let mut pool = RecyclingPool::<str, u32>::new();
+/// let foo = pool.intern("foo").expect("failed to intern a value");
+/// pool.remove(foo).expect("failed to remove a value");
+///
+/// let bar = pool.intern("bar").expect("failed to intern a value");
+/// assert_eq!(Ok("bar"), pool.resolve(bar));
+/// assert_eq!(ErrorKind::StaleSymbol, pool.resolve(foo).unwrap_err().kind());
+/// ```
+///
+/// [`Intern`]: trait.Intern.html
+pub trait Remove {
+    /// Type used to represent interned values.
+    type Symbol: Symbol;
+
+    /// Remove the value represented by a symbol from the interner.  Fails if
+    /// the symbol was not created by the interner, or if its value has
+    /// already been removed.
+    fn remove(self, symbol: Self::Symbol) -> Result<()>;
 }
 
 
@@ -265,3 +504,115 @@ pub trait Len {
     /// Check if the interner is "empty", i.e. has zero stored values.
     fn is_empty(&self) -> bool;
 }
+
+
+/// Trait for interners that can pre-allocate storage for values.
+///
+/// Reserving space up front can avoid repeated reallocation when the number
+/// of values to be interned is roughly known in advance.
+pub trait Capacity {
+    /// Fetch the number of values the interner can hold without
+    /// reallocating.
+    fn capacity(&self) -> usize;
+
+    /// Reserve space for at least `additional` more values.
+    ///
+    /// Like the corresponding methods on standard-library collections, this
+    /// method aborts the process if memory allocation fails; use
+    /// [`try_reserve`](#tymethod.try_reserve) to handle such failures.
+    fn reserve(&mut self, additional: usize);
+
+    /// Try to reserve space for at least `additional` more values, returning
+    /// an error with kind `ErrorKind::AllocationFailed` if the required
+    /// memory could not be allocated.
+    fn try_reserve(&mut self, additional: usize) -> Result<()>;
+
+    /// Release as much unused storage as possible.
+    fn shrink_to_fit(&mut self);
+}
+
+
+/// Trait for interners that can be reset to an empty state while keeping
+/// their allocated storage for reuse.
+///
+/// Clearing an interner invalidates every symbol it has created.  Most
+/// symbol types cannot detect this, so resolving a symbol from before a clear
+/// may yield an unrelated value; symbols created by the [`Luma`] adaptor,
+/// however, borrow their pool, and so cannot outlive a call to `clear`:
+///
+/// ```rust,compile_fail file="tests/compile-fail/luma-symbols-cannot-outlive-clear.rs"
+/// use symtern::prelude::*;
+/// use symtern::Pool;
+/// use symtern::adaptors::Luma;
+///
+/// let mut pool = Luma::from(Pool::<str, u32>::new());
+/// let sym = pool.intern("request-scoped").expect("failed to intern a value");
+/// pool.clear(); //~ ERROR cannot borrow `pool` as mutable because it is also borrowed as immutable
+/// println!("{}", &*pool.resolve(sym).expect("failed to resolve a value"));
+/// ```
+///
+/// [`Luma`]: ../adaptors/struct.Luma.html
+pub trait Clear {
+    /// Remove all values from the interner, keeping its allocated capacity.
+    fn clear(&mut self);
+}
+
+
+/// Memory use and hashing statistics reported by the [`Stats`] trait.
+///
+/// Byte counts are estimates: they include the storage owned directly by an
+/// interner and its values, but not allocator overhead.
+///
+/// [`Stats`]: trait.Stats.html
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PoolStats {
+    /// Number of values stored by the interner.
+    pub len: usize,
+    /// Total size of the stored values themselves, in bytes.
+    pub data_bytes: usize,
+    /// Size of the interner's index, value table and other bookkeeping, in
+    /// bytes.  For values stored directly in the value table (such as
+    /// integers), this includes the values' own storage.
+    pub overhead_bytes: usize,
+    /// Fraction of the index's capacity currently in use.
+    pub load_factor: f64,
+    /// Number of stored values whose hash is the same as that of another
+    /// stored value.  A hasher that works well for the stored values should
+    /// produce very few of these.
+    pub collisions: usize,
+    /// Number of successful `intern` calls answered by storing the value in
+    /// the symbol itself, by an [`Inline`] adaptor.
+    ///
+    /// [`Inline`]: ../adaptors/struct.Inline.html
+    pub inlined_interns: usize,
+    /// Number of successful `intern` calls an [`Inline`] adaptor passed on to
+    /// its wrapped pool.
+    ///
+    /// [`Inline`]: ../adaptors/struct.Inline.html
+    pub wrapped_interns: usize,
+}
+
+/// Trait for interners that can report statistics about their contents and
+/// memory use.
+///
+/// Adaptors report the statistics of the pool they wrap, together with any
+/// of their own.
+///
+/// ```rust
+/// use symtern::prelude::*;
+/// use symtern::Pool;
+/// use symtern::adaptors::Inline;
+///
+/// let mut pool = Inline::<Pool<str, u64>>::new();
+/// pool.intern("short").expect("failed to intern a value");
+/// pool.intern("a much longer value").expect("failed to intern a value");
+///
+/// let stats = pool.stats();
+/// assert_eq!(1, stats.len);
+/// assert_eq!(19, stats.data_bytes);
+/// assert_eq!((1, 1), (stats.inlined_interns, stats.wrapped_interns));
+/// ```
+pub trait Stats {
+    /// Collect statistics about the interner.
+    fn stats(&self) -> PoolStats;
+}
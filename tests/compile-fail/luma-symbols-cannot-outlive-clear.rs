@@ -0,0 +1,21 @@
+// Copyright (C) 2016-2017 Symtern Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-Apache
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or
+// distributed except according to those terms.
+//! Test that symbols created by the `Luma` adaptor cannot be used after the
+//! adaptor has been cleared.
+
+extern crate symtern;
+use symtern::prelude::*;
+use symtern::Pool;
+use symtern::adaptors::Luma;
+
+fn main() {
+    let mut pool = Luma::from(Pool::<str, u32>::new());
+    let sym = pool.intern("request-scoped").expect("failed to intern a value");
+    pool.clear(); //~ ERROR cannot borrow `pool` as mutable because it is also borrowed as immutable
+    println!("{}", &*pool.resolve(sym).expect("failed to resolve a value"));
+}
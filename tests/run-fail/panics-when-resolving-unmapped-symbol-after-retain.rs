@@ -0,0 +1,23 @@
+// Copyright (C) 2016-2017 Symtern Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-Apache
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or
+// distributed except according to those terms.
+
+// error-pattern:Detected an invalid attempt to resolve a symbol
+#![cfg(debug_assertions)]
+extern crate symtern;
+use symtern::prelude::*;
+use symtern::Pool;
+
+fn main() {
+    let mut pool = Pool::<str,u16>::new();
+    let sym = pool.intern("foo").unwrap();
+
+    // `retain` renumbers the pool's values, so `sym` must be remapped before
+    // it can be used again.
+    pool.retain(|_, _| true);
+    pool.resolve(sym).unwrap();
+}
@@ -0,0 +1,32 @@
+// Copyright (C) 2016-2017 Symtern Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-Apache
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or
+// distributed except according to those terms.
+
+// error-pattern:which does not have its most-significant bit set
+extern crate symtern;
+use symtern::adaptors::Pack;
+use symtern::adaptors::testkit::check_pack;
+
+/// Encoder that forgets to tag its IDs, so they could collide with IDs
+/// assigned by the wrapped pool.
+struct Untagged;
+
+impl Pack<u8, u32> for Untagged {
+    type Unpacked<'a> = &'a u8;
+
+    fn pack(value: &u8) -> Option<u32> {
+        Some(u32::from(*value))
+    }
+
+    fn unpack(_: &u32) -> Option<&u8> {
+        None
+    }
+}
+
+fn main() {
+    check_pack::<Untagged, u8, u32>(&[&1]);
+}